@@ -3,7 +3,7 @@ use std::{convert::TryFrom, sync::mpsc::Sender};
 
 use bm_bluetooth::*;
 use bm_grainfather;
-use bm_grainfather::btleplug::Client as GrainfatherClient;
+use bm_grainfather::Client as GrainfatherClient;
 use bm_tilt::*;
 
 use ::btleplug::api::Central;
@@ -24,7 +24,8 @@ use ::btleplug::corebluetooth::{adapter::Adapter as CentralImpl, manager::Manage
 
 pub enum BluetoothDiscoveryEvent {
     DiscoveredTilt(Tilt),
-    DiscoveredGrainfather(GrainfatherClient),
+    /// A grainfather with the given address was found, and connected to.
+    DiscoveredGrainfather(String, GrainfatherClient),
 }
 
 pub struct BluetoothDiscovery<'z> {
@@ -123,7 +124,9 @@ impl<'z> BluetoothDiscovery<'z> {
 
                             info!("Connected to the grainfather peripheral with address {}", address);
 
-                            self.sender.send(BluetoothDiscoveryEvent::DiscoveredGrainfather(gf)).unwrap();
+                            self.sender
+                                .send(BluetoothDiscoveryEvent::DiscoveredGrainfather(address.to_string(), gf))
+                                .unwrap();
                         } else {
                             info!("Couldn't locate grainfather peripheral in btleplug, will wait until discovery happens again");
                         }
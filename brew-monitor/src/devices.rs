@@ -1 +1,2 @@
 pub mod gf_manager;
+pub mod gf_registry;
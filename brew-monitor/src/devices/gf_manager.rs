@@ -1,10 +1,22 @@
 use bm_grainfather::{
-    btleplug::Client as GrainfatherClient, notifications::*, Command, InteractionCode, Notification, Recipe, StepNumber,
+    calc,
+    capture::Direction,
+    notifications::*,
+    trace::{Decoded, TraceEntry},
+    BrewPhase, BrewSession, Client as GrainfatherClient, Command, Error, InteractionCode, Notification, Recipe,
+    StepNumber, Units, UploadOptions, UploadProgress, Voltage,
 };
+use bm_units::{celsius, CentiCelsius};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use std::fs::OpenOptions;
+use std::path::PathBuf;
 use std::sync::{
     mpsc::{self, Receiver, Sender},
     Arc, Mutex, MutexGuard,
 };
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 #[derive(Clone, Debug)]
 pub enum ManagerOrClientNotification {
@@ -15,8 +27,72 @@ pub enum ManagerOrClientNotification {
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum ManagerNotification {
+    /// The link to the controller has been established, or re-established.
+    Connected,
+    /// The link to the controller has been lost, reconnection is being attempted.
+    Disconnected,
     BoilAlertState(BoilAlertState),
     HeatSpargeWaterAlertState(HeatSpargeWaterAlertState),
+    SessionState(SessionState),
+    RecipeUploadProgress(UploadProgress),
+    /// The controller has answered the handshake made on connecting.
+    Info(GrainfatherInfo),
+}
+
+/// What the controller reports about itself when asked, each detail is absent until
+/// the controller has answered.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct GrainfatherInfo {
+    pub firmware_version: Option<String>,
+    pub voltage: Option<Voltage>,
+    /// The units shown on the controller's display.
+    pub units: Option<Units>,
+    #[serde(default, with = "celsius::option")]
+    pub boil_temperature: Option<CentiCelsius>,
+    /// The power currently drawn by the heating element, this depends on the voltage.
+    pub heat_power_watts: Option<u32>,
+}
+
+/// A frame exchanged with the controller, with the command or notification it decodes to.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct TraceEvent {
+    pub timestamp: DateTime<Utc>,
+    pub direction: Direction,
+    /// The frame as text, with any bytes that aren't valid UTF-8 replaced.
+    pub data: String,
+    pub bytes: Vec<u8>,
+    pub command: Option<Command>,
+    pub notification: Option<Notification>,
+}
+
+impl TraceEvent {
+    fn new(entry: &TraceEntry) -> Self {
+        let (command, notification) = match &entry.decoded {
+            Decoded::Command(command) => (Some(command.clone()), None),
+            Decoded::Notification(notification) => (None, Some(notification.clone())),
+            Decoded::Other => (None, None),
+        };
+
+        Self {
+            timestamp: DateTime::from(entry.timestamp),
+            direction: entry.direction,
+            data: String::from_utf8_lossy(&entry.data).into_owned(),
+            bytes: entry.data.clone(),
+            command,
+            notification,
+        }
+    }
+
+    /// The type of the command or notification, as it's named when serialized.
+    pub fn type_name(&self) -> Option<String> {
+        let value = match (&self.command, &self.notification) {
+            (Some(command), _) => serde_json::to_value(command),
+            (_, Some(notification)) => serde_json::to_value(notification),
+            (None, None) => return None,
+        };
+
+        value.ok()?.get("type")?.as_str().map(String::from)
+    }
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -29,10 +105,27 @@ pub struct HeatSpargeWaterAlertState {
     pub visible: bool,
 }
 
+/// The progress through the most recently sent recipe, the phase is absent
+/// if no recipe has been sent.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SessionState {
+    pub phase: Option<BrewPhase>,
+    pub active_boil_addition: Option<u8>,
+    pub remaining_seconds: Option<u64>,
+}
+
 #[derive(Clone)]
 pub struct GrainfatherManager(Arc<Mutex<GrainfatherInternal>>);
 
 impl GrainfatherManager {
+    /// How often the link to the controller is checked.
+    const CONNECTION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// The delay before the first attempt to reconnect, this doubles with each failed
+    /// attempt up to the maximum.
+    const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+    const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(60);
+
     pub fn new() -> Self {
         Self(Arc::new(Mutex::new(GrainfatherInternal::new())))
     }
@@ -41,26 +134,186 @@ impl GrainfatherManager {
         self.lock().set_client(client);
     }
 
-    pub fn command(&self, command: &Command) -> Result<(), btleplug::Error> {
-        self.lock().command(command)
+    /// Captures the frames exchanged with each client that is subsequently set, appending
+    /// them to the file at the given path.
+    pub fn capture_to(&self, path: PathBuf) {
+        self.lock().capture_path = Some(path);
     }
 
-    pub fn send_recipe(&self, recipe: &Recipe) -> Result<(), btleplug::Error> {
-        self.lock().send_recipe(recipe)
+    /// Determines whether the link to the controller is established, as of the last check.
+    pub fn is_connected(&self) -> bool {
+        self.lock().connected
+    }
+
+    /// Despatches a command to the controller, waiting for it to be written without
+    /// holding the manager's lock, as it may be queued behind a recipe.
+    pub async fn command(&self, command: &Command) -> Result<(), Error> {
+        let (client, state) = {
+            let gf = self.lock();
+            (gf.client.clone().ok_or(Error::NotConnected)?, gf.state.clone())
+        };
+
+        client.command_async(command).await?;
+
+        state.lock().unwrap().handle_command(command);
+        Ok(())
+    }
+
+    /// Uploads a recipe, reporting progress to subscribers as it goes. The brew session only
+    /// starts once the controller has confirmed that it loaded the recipe.
+    pub async fn upload_recipe(&self, recipe: &Recipe) -> Result<(), Error> {
+        let (client, subscribers) = {
+            let gf = self.lock();
+            let client = gf.client.clone().ok_or(Error::NotConnected)?;
+            (client, gf.subscribers.clone())
+        };
+
+        client
+            .upload_recipe(recipe, &UploadOptions::default(), |progress| {
+                send_notification_to_subscribers(
+                    subscribers.as_ref(),
+                    &ManagerOrClientNotification::ManagerNotification(ManagerNotification::RecipeUploadProgress(
+                        progress,
+                    )),
+                );
+            })
+            .await?;
+
+        self.lock().start_session(recipe);
+        Ok(())
     }
 
     pub fn subscribe(&mut self) -> Receiver<ManagerOrClientNotification> {
         self.lock().subscribe()
     }
 
+    /// Receives every frame exchanged with the controller from now on, until the receiver
+    /// is dropped.
+    pub fn trace(&self) -> UnboundedReceiver<TraceEvent> {
+        let (sender, receiver) = unbounded_channel();
+        self.lock().tracers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Watches the link to the controller, announcing when it's lost and re-established,
+    /// and reconnecting with an increasing delay between attempts. This never returns.
+    pub async fn monitor_connection(self) {
+        let mut reconnect_delay = Self::RECONNECT_DELAY_MIN;
+
+        loop {
+            let (client, subscribed) = {
+                let gf = self.lock();
+                (gf.client.clone(), gf.subscribed)
+            };
+
+            let client = match client {
+                Some(client) => client,
+
+                None => {
+                    tokio::time::delay_for(Self::CONNECTION_CHECK_INTERVAL).await;
+                    continue;
+                }
+            };
+
+            // Reconnecting also subscribes again, which is how a failed subscription is retried
+            if subscribed && client.is_connected() {
+                reconnect_delay = Self::RECONNECT_DELAY_MIN;
+
+                let connected = self.lock().set_connected(true);
+
+                if connected {
+                    self.handshake(&client).await;
+                }
+
+                tokio::time::delay_for(Self::CONNECTION_CHECK_INTERVAL).await;
+                continue;
+            }
+
+            self.lock().set_connected(false);
+
+            info!("Reconnecting to the grainfather in {:?}", reconnect_delay);
+            tokio::time::delay_for(reconnect_delay).await;
+            reconnect_delay = (reconnect_delay * 2).min(Self::RECONNECT_DELAY_MAX);
+
+            // Connecting blocks, so keep it away from the other tasks
+            let reconnecting = client.clone();
+
+            match tokio::task::spawn_blocking(move || reconnecting.reconnect()).await {
+                Ok(Ok(())) => {
+                    info!("Reconnected to the grainfather");
+
+                    let mut gf = self.lock();
+
+                    // Unless it was replaced while reconnecting
+                    if gf.client.as_ref().map(|current| Arc::ptr_eq(current, &client)).unwrap_or(false) {
+                        gf.subscribed = true;
+                    }
+                }
+
+                Ok(Err(err)) => warn!("Unable to reconnect to the grainfather: {:?}", err),
+                Err(err) => error!("Grainfather reconnection failed to complete: {:?}", err),
+            }
+        }
+    }
+
+    /// The details the controller reported during the handshake, along with anything
+    /// derived from them.
+    pub fn info(&self) -> GrainfatherInfo {
+        self.lock().state.lock().unwrap().build_info()
+    }
+
+    /// Queries the controller for the details it only reports on request, caching its
+    /// answers until the link is next lost, then announces them to subscribers.
+    async fn handshake(&self, client: &GrainfatherClient) {
+        let firmware_version = client.firmware_version().await;
+        let voltage_and_units = client.voltage_and_units().await;
+        let boil_temperature = client.boil_temperature().await;
+
+        let gf = self.lock();
+
+        let info = {
+            let mut state = gf.state.lock().unwrap();
+
+            match firmware_version {
+                Ok(firmware_version) => state.firmware_version = Some(firmware_version),
+                Err(err) => warn!("Unable to query the grainfather firmware version: {:?}", err),
+            }
+
+            match voltage_and_units {
+                Ok(voltage_and_units) => state.voltage_and_units = Some(voltage_and_units),
+                Err(err) => warn!("Unable to query the grainfather voltage and units: {:?}", err),
+            }
+
+            match boil_temperature {
+                Ok(boil_temperature) => state.boil_temperature = Some(boil_temperature),
+                Err(err) => warn!("Unable to query the grainfather boil temperature: {:?}", err),
+            }
+
+            state.build_info()
+        };
+
+        send_notification_to_subscribers(
+            gf.subscribers.as_ref(),
+            &ManagerOrClientNotification::ManagerNotification(ManagerNotification::Info(info)),
+        );
+    }
+
     fn lock(&self) -> MutexGuard<GrainfatherInternal> {
         self.0.lock().expect("The grainfather manager lock has been poisoned")
     }
 }
 
 struct GrainfatherInternal {
-    client: Option<GrainfatherClient>,
+    client: Option<Arc<GrainfatherClient>>,
+    connected: bool,
+
+    /// Whether notifications are being delivered from the client, if subscribing fails
+    /// the client is left to be subscribed again when it's reconnected.
+    subscribed: bool,
+
+    capture_path: Option<PathBuf>,
     subscribers: Arc<Mutex<Vec<Sender<ManagerOrClientNotification>>>>,
+    tracers: Arc<Mutex<Vec<UnboundedSender<TraceEvent>>>>,
     state: Arc<Mutex<State>>,
 }
 
@@ -70,55 +323,107 @@ impl GrainfatherInternal {
     fn new() -> Self {
         Self {
             client: None,
+            connected: false,
+            subscribed: false,
+            capture_path: None,
             subscribers: Arc::new(Mutex::new(Vec::with_capacity(Self::INITIAL_HANDLER_CAPACITY))),
+            tracers: Arc::new(Mutex::new(Vec::new())),
             state: Arc::new(Mutex::new(State::default())),
         }
     }
 
     fn set_client(&mut self, client: GrainfatherClient) {
-        let have_valid_client = self.client.as_ref().map(|client| client.is_connected()).unwrap_or(false);
+        let have_valid_client =
+            self.subscribed && self.client.as_ref().map(|client| client.is_connected()).unwrap_or(false);
 
         if !have_valid_client {
-            println!("Setting grainfather");
+            info!("Setting grainfather");
+
+            let client = match &self.capture_path {
+                Some(path) => match OpenOptions::new().create(true).append(true).open(path) {
+                    Ok(file) => client.with_capture(file),
+
+                    Err(err) => {
+                        error!("Unable to open grainfather capture {:?}: {:?}", path, err);
+                        client
+                    }
+                },
+
+                None => client,
+            };
 
             let subscribers = self.subscribers.clone();
             let state = self.state.clone();
 
-            client
-                .subscribe(Box::new(move |notification| {
-                    send_notification_to_subscribers(
-                        subscribers.as_ref(),
-                        &ManagerOrClientNotification::ClientNotification(notification.clone()),
-                    );
-
-                    // Sometimes this will generate an synthetic notification, e.g.
-                    // for boil additions
-                    if let Some(synthetic_notifications) = state.lock().unwrap().handle_notification(&notification) {
-                        for synthetic_notification in synthetic_notifications.iter() {
-                            send_notification_to_subscribers(subscribers.as_ref(), &synthetic_notification);
-                        }
+            let subscribed = client.subscribe(Box::new(move |notification| {
+                send_notification_to_subscribers(
+                    subscribers.as_ref(),
+                    &ManagerOrClientNotification::ClientNotification(notification.clone()),
+                );
+
+                // Sometimes this will generate an synthetic notification, e.g.
+                // for boil additions
+                if let Some(synthetic_notifications) = state.lock().unwrap().handle_notification(&notification) {
+                    for synthetic_notification in synthetic_notifications.iter() {
+                        send_notification_to_subscribers(subscribers.as_ref(), &synthetic_notification);
                     }
-                }))
-                .unwrap();
+                }
+            }));
+
+            self.subscribed = match subscribed {
+                Ok(()) => true,
+
+                Err(err) => {
+                    error!("Unable to subscribe to grainfather notifications: {:?}", err);
+                    false
+                }
+            };
+
+            let tracers = self.tracers.clone();
+
+            client.set_tracer(Some(Box::new(move |entry| {
+                let mut tracers = tracers.lock().unwrap();
+
+                if !tracers.is_empty() {
+                    let event = TraceEvent::new(entry);
+                    tracers.retain(|tracer| tracer.send(event.clone()).is_ok());
+                }
+            })));
 
-            self.client = Some(client);
+            // The connection monitor announces the new client once it sees it's connected
+            self.set_connected(false);
+            self.client = Some(Arc::new(client));
         }
     }
 
-    pub fn command(&mut self, command: &Command) -> Result<(), btleplug::Error> {
-        let client = self.client.as_ref().ok_or(btleplug::Error::NotConnected)?;
-        let result = client.command(command);
-
-        if let Ok(()) = result {
-            self.state.lock().unwrap().handle_command(&command);
+    /// Records the state of the link to the controller, announcing any change to subscribers.
+    /// The state reported before the link was re-established is discarded, as it may be
+    /// stale. Returns whether the state of the link changed.
+    fn set_connected(&mut self, connected: bool) -> bool {
+        if self.connected == connected {
+            return false;
         }
 
-        result
+        self.connected = connected;
+
+        let notification = if connected {
+            self.state.lock().unwrap().reset();
+            ManagerNotification::Connected
+        } else {
+            ManagerNotification::Disconnected
+        };
+
+        send_notification_to_subscribers(
+            self.subscribers.as_ref(),
+            &ManagerOrClientNotification::ManagerNotification(notification),
+        );
+
+        true
     }
 
-    pub fn send_recipe(&mut self, recipe: &Recipe) -> Result<(), btleplug::Error> {
-        let client = self.client.as_ref().ok_or(btleplug::Error::NotConnected)?;
-        client.send_recipe(recipe)
+    pub fn start_session(&mut self, recipe: &Recipe) {
+        let session_state = self.state.lock().unwrap().start_session(recipe.clone());
+        send_notification_to_subscribers(self.subscribers.as_ref(), &session_state);
     }
 
     pub fn subscribe(&mut self) -> Receiver<ManagerOrClientNotification> {
@@ -147,17 +452,31 @@ struct State {
     manual_power_mode: bool,
     sparge_water_alert_displayed: bool,
     // Temp
-    temp_desired: i32,
-    temp_current: i32,
+    temp_desired: CentiCelsius,
+    temp_current: CentiCelsius,
     // Timer
     timer_active: bool,
     // Boil Alert Visible
     boil_alert_active: bool,
     // Heat Sparge Water Alert Visible
     sparge_water_alert_active: bool,
+    // Only reported on request
+    firmware_version: Option<String>,
+    voltage_and_units: Option<VoltageAndUnits>,
+    boil_temperature: Option<CentiCelsius>,
+    // The most recently sent recipe
+    session: Option<BrewSession>,
 }
 
 impl State {
+    /// Discards everything reported by the controller, retaining the session.
+    fn reset(&mut self) {
+        *self = Self {
+            session: self.session.take(),
+            ..Self::default()
+        };
+    }
+
     fn handle_command(&mut self, command: &Command) -> Option<ManagerOrClientNotification> {
         let maybe_dismiss_alert = match command {
             Command::DismissAlert => true,
@@ -178,6 +497,11 @@ impl State {
         None
     }
 
+    fn start_session(&mut self, recipe: Recipe) -> ManagerOrClientNotification {
+        self.session = Some(BrewSession::new(recipe));
+        self.build_session_status()
+    }
+
     fn update_boil_alert_status(&mut self, boil_alert_active: bool) -> ManagerOrClientNotification {
         maybe_update("boil_alert_visible", &mut self.boil_alert_active, &boil_alert_active);
         self.build_boil_status()
@@ -189,6 +513,10 @@ impl State {
     }
 
     fn handle_notification(&mut self, notification: &Notification) -> Option<Vec<ManagerOrClientNotification>> {
+        if let Some(session) = self.session.as_mut() {
+            session.handle_notification(notification);
+        }
+
         match notification {
             Notification::Status1(Status1 {
                 heat_active,
@@ -212,7 +540,11 @@ impl State {
                 // Send out boil addition alerts with each status alert
                 // TODO: if we stored the recipe, we could work out whether we were in the boil,
                 // and only send them then
-                return Some(vec![self.build_boil_status(), self.build_sparge_water_status()]);
+                return Some(vec![
+                    self.build_boil_status(),
+                    self.build_sparge_water_status(),
+                    self.build_session_status(),
+                ]);
             }
 
             Notification::Status2(Status2 {
@@ -245,8 +577,8 @@ impl State {
             }) => {
                 // These frequently fluctuate by 0.1 of a degree, which is
                 // annoying to report, so don't
-                self.temp_desired = (desired * 100.0) as i32;
-                self.temp_current = (current * 100.0) as i32;
+                self.temp_desired = *desired;
+                self.temp_current = *current;
             }
 
             Notification::Timer(Timer {
@@ -259,7 +591,7 @@ impl State {
             Notification::Interaction(Interaction {
                 interaction_code,
             }) => {
-                println!("[R]: interaction with code {:?}", interaction_code);
+                debug!("Received interaction with code {:?}", interaction_code);
 
                 if let InteractionCode::Dismiss = interaction_code {
                     if self.boil_alert_active {
@@ -280,20 +612,71 @@ impl State {
                 return Some(vec![self.update_sparge_water_alert_status(true)]);
             }
 
+            Notification::FirmwareVersion(FirmwareVersion {
+                firmware_version,
+            }) => {
+                debug!("Received firmware version {}", firmware_version);
+                self.firmware_version = Some(firmware_version.clone());
+            }
+
+            Notification::VoltageAndUnits(voltage_and_units) => {
+                debug!("Received {:?}", voltage_and_units);
+                self.voltage_and_units = Some(voltage_and_units.clone());
+            }
+
+            Notification::Boil(Boil {
+                boil_temperature,
+            }) => {
+                debug!("Received boil temperature {}", boil_temperature);
+                self.boil_temperature = Some(*boil_temperature);
+            }
+
             other => {
-                println!("[R]: {:?}", other);
+                debug!("Received {:?}", other);
             }
         }
 
         None
     }
 
+    fn build_info(&self) -> GrainfatherInfo {
+        let voltage = self.voltage_and_units.as_ref().map(|voltage_and_units| voltage_and_units.voltage.clone());
+
+        GrainfatherInfo {
+            firmware_version: self.firmware_version.clone(),
+            heat_power_watts: voltage
+                .as_ref()
+                .map(|voltage| calc::heat_power_watts(voltage, self.heat_power_output_percentage)),
+            voltage,
+            units: self.voltage_and_units.as_ref().map(|voltage_and_units| voltage_and_units.units.clone()),
+            boil_temperature: self.boil_temperature,
+        }
+    }
+
     fn build_boil_status(&self) -> ManagerOrClientNotification {
         ManagerOrClientNotification::ManagerNotification(ManagerNotification::BoilAlertState(BoilAlertState {
             visible: self.boil_alert_active,
         }))
     }
 
+    fn build_session_status(&self) -> ManagerOrClientNotification {
+        let session_state = match &self.session {
+            Some(session) => SessionState {
+                phase: Some(session.phase()),
+                active_boil_addition: session.active_boil_addition(),
+                remaining_seconds: session.time_remaining().map(|remaining| remaining.as_secs()),
+            },
+
+            None => SessionState {
+                phase: None,
+                active_boil_addition: None,
+                remaining_seconds: None,
+            },
+        };
+
+        ManagerOrClientNotification::ManagerNotification(ManagerNotification::SessionState(session_state))
+    }
+
     fn build_sparge_water_status(&self) -> ManagerOrClientNotification {
         ManagerOrClientNotification::ManagerNotification(ManagerNotification::HeatSpargeWaterAlertState(
             HeatSpargeWaterAlertState {
@@ -311,7 +694,7 @@ where
         return;
     }
 
-    println!("[R]: {} changed from {:?} to {:?}", field_name, target, new_value);
+    debug!("{} changed from {:?} to {:?}", field_name, target, new_value);
 
     *target = new_value.clone();
 }
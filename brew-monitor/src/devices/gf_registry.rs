@@ -0,0 +1,146 @@
+use crate::devices::gf_manager::GrainfatherManager;
+
+use bm_grainfather::Client as GrainfatherClient;
+use log::{info, warn};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A controller known to the registry.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ControllerInfo {
+    pub address: String,
+    pub alias: Option<String>,
+    pub connected: bool,
+}
+
+/// Possible reasons for an alias to be refused.
+#[derive(Debug)]
+pub enum AliasError {
+    /// The alias is the address of another controller, which it would hide.
+    IsAnAddress(String),
+}
+
+/// Keeps a manager for each controller seen, keyed by its address, usually the bluetooth
+/// address. Each controller's state, alerts and subscribers are kept by its own manager.
+#[derive(Clone)]
+pub struct GrainfatherRegistry(Arc<Mutex<RegistryInternal>>);
+
+struct RegistryInternal {
+    controllers: HashMap<String, GrainfatherManager>,
+    /// The address of the controller known by each alias.
+    aliases: HashMap<String, String>,
+    capture_path: Option<PathBuf>,
+}
+
+impl GrainfatherRegistry {
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(RegistryInternal {
+            controllers: HashMap::new(),
+            aliases: HashMap::new(),
+            capture_path: None,
+        })))
+    }
+
+    /// Names the controller with the given address, this may be done before it has been seen.
+    /// This replaces the controller's previous alias, and any other controller with the same
+    /// alias loses it. Aliases which are the address of another controller are refused.
+    pub fn set_alias(&self, address: &str, alias: &str) -> Result<(), AliasError> {
+        let mut registry = self.lock();
+
+        let is_an_address =
+            registry.controllers.contains_key(alias) || registry.aliases.values().any(|aliased| aliased == alias);
+
+        if alias != address && is_an_address {
+            return Err(AliasError::IsAnAddress(alias.to_string()));
+        }
+
+        registry.aliases.retain(|_, aliased| aliased != address);
+        registry.aliases.insert(alias.to_string(), address.to_string());
+        Ok(())
+    }
+
+    /// Captures the frames exchanged with each controller subsequently seen, each controller
+    /// is captured to its own file alongside the given path, named after its address.
+    pub fn capture_to(&self, path: PathBuf) {
+        self.lock().capture_path = Some(path);
+    }
+
+    /// Hands a client for the controller with the given address to its manager, creating the
+    /// manager, and starting to monitor its connection, if the controller hasn't been seen before.
+    pub fn set_client(&self, address: &str, client: GrainfatherClient) {
+        let mut registry = self.lock();
+
+        let manager = match registry.controllers.get(address) {
+            Some(manager) => manager.clone(),
+
+            None => {
+                info!("Registering the grainfather with address {}", address);
+
+                // An alias can only have been given this address before the controller was seen
+                if let Some(aliased) = registry.aliases.remove(address) {
+                    warn!("Removing the alias {} for {}, as it's the address of another grainfather", address, aliased);
+                }
+
+                let manager = GrainfatherManager::new();
+
+                if let Some(path) = &registry.capture_path {
+                    manager.capture_to(capture_path(path, address));
+                }
+
+                tokio::spawn(manager.clone().monitor_connection());
+                registry.controllers.insert(address.to_string(), manager.clone());
+                manager
+            }
+        };
+
+        drop(registry);
+        manager.set_client(client);
+    }
+
+    /// Finds the manager of the controller with the given alias, or failing that, address.
+    pub fn get(&self, name: &str) -> Option<GrainfatherManager> {
+        let registry = self.lock();
+        let address = registry.aliases.get(name).map(String::as_str).unwrap_or(name);
+
+        registry.controllers.get(address).cloned()
+    }
+
+    /// Describes every controller seen, ordered by address.
+    pub fn list(&self) -> Vec<ControllerInfo> {
+        let registry = self.lock();
+
+        let mut controllers = registry
+            .controllers
+            .iter()
+            .map(|(address, manager)| ControllerInfo {
+                address: address.clone(),
+                alias: registry.aliases.iter().find(|(_, aliased)| *aliased == address).map(|(alias, _)| alias.clone()),
+                connected: manager.is_connected(),
+            })
+            .collect::<Vec<_>>();
+
+        controllers.sort_by(|a, b| a.address.cmp(&b.address));
+        controllers
+    }
+
+    fn lock(&self) -> MutexGuard<RegistryInternal> {
+        self.0.lock().expect("The grainfather registry lock has been poisoned")
+    }
+}
+
+/// The path of the capture for the controller with the given address, e.g. a capture path of
+/// `gf.jsonl` gives `gf-AA-BB-CC-DD-EE-FF.jsonl`.
+fn capture_path(path: &Path, address: &str) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    let address = address.replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+
+    let mut file_name = format!("{}-{}", stem, address);
+
+    if let Some(extension) = path.extension() {
+        file_name.push('.');
+        file_name.push_str(&extension.to_string_lossy());
+    }
+
+    path.with_file_name(file_name)
+}
@@ -2,12 +2,14 @@ mod bluetooth_discovery;
 pub use bluetooth_discovery::*;
 
 mod devices;
-use devices::gf_manager::GrainfatherManager;
+use devices::gf_registry::GrainfatherRegistry;
 
 mod web;
 
 use bm_db::DB;
+use bm_grainfather::{simulator::Simulator, transport::TcpTransport, Client as GrainfatherClient};
 use bm_tilt::*;
+use bm_units::CentiCelsius;
 use chrono::prelude::*;
 use dht22_pi as dht22;
 use log::error;
@@ -25,7 +27,40 @@ pub async fn main() {
 
     let db = DB::open("brew-monitor.db").unwrap();
     let tilts = Arc::new(RwLock::new(HashMap::<TiltColor, DeviceInfo<Tilt>>::new()));
-    let gf = GrainfatherManager::new();
+    let gf = GrainfatherRegistry::new();
+
+    if let Some(path) = arg_value("--capture-grainfather") {
+        gf.capture_to(path.into());
+    }
+
+    // Each alias is given as the controller's address and its alias, e.g. AA:BB:CC:DD:EE:FF=left
+    for arg in arg_values("--grainfather-alias") {
+        let mut parts = arg.splitn(2, '=');
+
+        match (parts.next(), parts.next()) {
+            (Some(address), Some(alias)) => {
+                if let Err(err) = gf.set_alias(address, alias) {
+                    error!("Unable to alias the grainfather {} as {}: {:?}", address, alias, err);
+                }
+            }
+
+            _ => error!("Grainfather aliases must be given as <address>=<alias>, not {}", arg),
+        }
+    }
+
+    // A simulated controller appears alongside any real ones, with the address "simulator"
+    if std::env::args().any(|arg| arg == "--simulate-grainfather") {
+        gf.set_client("simulator", GrainfatherClient::simulated(Simulator::new(), 1.0));
+    }
+
+    // Likewise for a controller reached through a remote bluetooth gateway, whose address
+    // is that of the gateway
+    for addr in arg_values("--grainfather-gateway") {
+        match TcpTransport::connect(addr.as_str()) {
+            Ok(transport) => gf.set_client(&addr, GrainfatherClient::from_transport(transport)),
+            Err(err) => error!("Unable to connect to the grainfather gateway at {}: {:?}", addr, err),
+        }
+    }
 
     let routes = {
         let web_content = web::assets::route();
@@ -53,8 +88,9 @@ pub async fn main() {
                         let now = Utc::now();
                         println!("at={:?} celsius={:?} humidity={:?}", now, temperature, humidity);
 
-                        if let Err(err) = garage.insert_reading((temperature * 100.0) as u16, (humidity * 100.0) as u16)
-                        {
+                        let temperature = CentiCelsius::from_celsius(f64::from(temperature));
+
+                        if let Err(err) = garage.insert_reading(temperature, (humidity * 100.0) as u16) {
                             error!(
                                 "Unable to insert dht22 reading for {} with temperature {} and humidity {}: {:?}",
                                 "garage", temperature, humidity, err,
@@ -112,12 +148,9 @@ pub async fn main() {
                 match event {
                     BluetoothDiscoveryEvent::DiscoveredTilt(tilt) => {
                         let now = Utc::now();
-                        let centi_celsius = ((i32::from(tilt.fahrenheit) - 32) * 500) / 9;
+                        let celsius = CentiCelsius::from(tilt.fahrenheit);
 
-                        println!(
-                            "at={:?} which={:?} celsius={:?} gravity={:?}",
-                            now, tilt.color, centi_celsius, tilt.gravity
-                        );
+                        println!("at={:?} which={:?} celsius={} gravity={}", now, tilt.color, celsius, tilt.gravity);
 
                         // TODO: cache tilts
                         if let Err(err) = db.tilt_ensure(&tilt.color).insert_reading(tilt.fahrenheit, tilt.gravity) {
@@ -127,8 +160,8 @@ pub async fn main() {
                         tilts.write().unwrap().insert(tilt.color, DeviceInfo::new(now, tilt));
                     }
 
-                    BluetoothDiscoveryEvent::DiscoveredGrainfather(gf_client) => {
-                        gf.set_client(gf_client);
+                    BluetoothDiscoveryEvent::DiscoveredGrainfather(address, gf_client) => {
+                        gf.set_client(&address, gf_client);
                     }
                 }
             }
@@ -141,6 +174,18 @@ pub async fn main() {
     dht22_monitor.await.unwrap();
 }
 
+/// Finds the value following the given flag in the command line arguments.
+fn arg_value(flag: &str) -> Option<String> {
+    arg_values(flag).into_iter().next()
+}
+
+/// Finds the value following each occurrence of the given flag in the command line arguments.
+fn arg_values(flag: &str) -> Vec<String> {
+    let args = std::env::args().collect::<Vec<_>>();
+
+    args.windows(2).filter(|pair| pair[0] == flag).map(|pair| pair[1].clone()).collect()
+}
+
 pub struct DeviceInfo<T> {
     when: DateTime<Utc>,
     device: T,
@@ -1,51 +1,55 @@
 mod ws;
-use ws::GrainfatherWebSocketHandler;
+use ws::{GrainfatherTraceHandler, GrainfatherWebSocketHandler, TraceFilter};
 
-use crate::devices::gf_manager::GrainfatherManager;
+use crate::devices::{gf_manager::GrainfatherManager, gf_registry::GrainfatherRegistry};
 
 use bm_grainfather::{self as gf};
-use warp::{reject::Rejection, reply::Reply, ws::Ws, Filter};
+use warp::{http::StatusCode, reject::Rejection, reply::Reply, ws::Ws, Filter};
 
-pub fn route(gf: GrainfatherManager) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
-    let ws = {
-        let gf = gf.clone();
+/// Routes requests to each controller under `/gf/{alias}`, where the alias may also be the
+/// controller's address, `/gf` lists the controllers.
+pub fn route(registry: GrainfatherRegistry) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let list = {
+        let registry = registry.clone();
 
-        warp::path!("ws").and(warp::ws()).map(move |ws: Ws| {
-            let gf = gf.clone();
-
-            ws.on_upgrade(move |websocket| GrainfatherWebSocketHandler::run(gf, websocket))
-        })
+        warp::path::end().and(warp::get()).map(move || warp::reply::json(&registry.list()))
     };
 
-    let command = {
-        let gf = gf.clone();
+    let controller = warp::path::param::<String>().and_then(move |name: String| {
+        let registry = registry.clone();
 
-        warp::path!("command").and(warp::post()).and(warp::body::json()).and_then(move |command: gf::Command| {
-            let gf = gf.clone();
+        async move { registry.get(&name).ok_or_else(warp::reject::not_found) }
+    });
 
-            async move {
-                gf.command(&command)
-                    .map(|()| warp::reply::json(&GrainfatherResponse {}))
-                    .map_err(|error| btleplug_to_warp_error(error))
-            }
-        })
-    };
+    let ws = controller.clone().and(warp::path!("ws")).and(warp::ws()).map(|gf: GrainfatherManager, ws: Ws| {
+        ws.on_upgrade(move |websocket| GrainfatherWebSocketHandler::run(gf, websocket))
+    });
 
-    let recipe = {
-        let gf = gf.clone();
+    let trace = controller.clone().and(warp::path!("trace")).and(warp::ws()).and(warp::query::<TraceFilter>()).map(
+        |gf: GrainfatherManager, ws: Ws, filter: TraceFilter| {
+            ws.on_upgrade(move |websocket| GrainfatherTraceHandler::run(gf, filter, websocket))
+        },
+    );
 
-        warp::path!("recipe").and(warp::post()).and(warp::body::json()).and_then(move |recipe: gf::Recipe| {
-            let gf = gf.clone();
+    let info = controller
+        .clone()
+        .and(warp::path!("info"))
+        .and(warp::get())
+        .map(|gf: GrainfatherManager| warp::reply::json(&gf.info()));
 
-            async move {
-                gf.send_recipe(&recipe)
-                    .map(|()| warp::reply::json(&GrainfatherResponse {}))
-                    .map_err(|error| btleplug_to_warp_error(error))
-            }
-        })
-    };
+    let command = controller
+        .clone()
+        .and(warp::path!("command"))
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(|gf: GrainfatherManager, command: gf::Command| async move { reply(gf.command(&command).await) });
+
+    let recipe =
+        controller.and(warp::path!("recipe")).and(warp::post()).and(warp::body::json()).and_then(
+            |gf: GrainfatherManager, recipe: gf::Recipe| async move { reply(gf.upload_recipe(&recipe).await) },
+        );
 
-    warp::path("gf").and(command.or(recipe).or(ws))
+    warp::path("gf").and(list.or(info).or(command).or(recipe).or(ws).or(trace))
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -54,9 +58,109 @@ struct GrainfatherRequest {}
 #[derive(serde::Serialize, serde::Deserialize)]
 struct GrainfatherResponse {}
 
-fn btleplug_to_warp_error(error: btleplug::Error) -> Rejection {
-    match error {
-        btleplug::Error::NotConnected => warp::reject::not_found(),
-        _ => panic!("Unhandled bluetooth error {:?}", error),
-    }
+/// Describes why a request to the controller failed, alongside a message for people.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ErrorResponse {
+    message: String,
+    error: GrainfatherError,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "data")]
+enum GrainfatherError {
+    NotConnected,
+
+    /// Too many commands are already waiting to be written to the controller.
+    QueueFull {
+        depth: usize,
+    },
+
+    Transport {
+        error: String,
+    },
+
+    /// The controller didn't reply in time.
+    Timeout {
+        milliseconds: u128,
+    },
+
+    InvalidRecipe {
+        problems: Vec<gf::RecipeProblem>,
+    },
+
+    /// The command or recipe can't be encoded into frames.
+    Encode {
+        error: String,
+    },
+
+    /// The controller didn't report that it loaded the recipe.
+    RecipeNotLoaded {
+        attempts: u32,
+    },
+}
+
+/// Replies to a request to the controller, errors are reported with a JSON body
+/// rather than rejecting the request, so that they reach the client.
+fn reply(result: Result<(), gf::Error>) -> Result<warp::reply::Response, Rejection> {
+    let error = match result {
+        Ok(()) => return Ok(warp::reply::json(&GrainfatherResponse {}).into_response()),
+        Err(error) => error,
+    };
+
+    let message = error.to_string();
+
+    let (status, error) = match error {
+        gf::Error::NotConnected => (StatusCode::SERVICE_UNAVAILABLE, GrainfatherError::NotConnected),
+
+        gf::Error::QueueFull(depth) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            GrainfatherError::QueueFull {
+                depth,
+            },
+        ),
+
+        gf::Error::Transport(error) => (
+            StatusCode::BAD_GATEWAY,
+            GrainfatherError::Transport {
+                error: format!("{:?}", error),
+            },
+        ),
+
+        gf::Error::Timeout(timeout) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            GrainfatherError::Timeout {
+                milliseconds: timeout.as_millis(),
+            },
+        ),
+
+        gf::Error::Invalid(problems) => (
+            StatusCode::BAD_REQUEST,
+            GrainfatherError::InvalidRecipe {
+                problems,
+            },
+        ),
+
+        gf::Error::Encode(error) => (
+            StatusCode::BAD_REQUEST,
+            GrainfatherError::Encode {
+                error: format!("{:?}", error),
+            },
+        ),
+
+        gf::Error::NotLoaded {
+            attempts,
+        } => (
+            StatusCode::BAD_GATEWAY,
+            GrainfatherError::RecipeNotLoaded {
+                attempts,
+            },
+        ),
+    };
+
+    let reply = warp::reply::json(&ErrorResponse {
+        message,
+        error,
+    });
+
+    Ok(warp::reply::with_status(reply, status).into_response())
 }
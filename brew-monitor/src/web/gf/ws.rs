@@ -1,5 +1,6 @@
-use crate::devices::gf_manager::{GrainfatherManager, ManagerOrClientNotification};
+use crate::devices::gf_manager::{GrainfatherManager, ManagerOrClientNotification, TraceEvent};
 use futures::{SinkExt, StreamExt};
+use log::warn;
 use warp::ws::WebSocket;
 
 pub struct GrainfatherWebSocketHandler {}
@@ -24,9 +25,70 @@ impl GrainfatherWebSocketHandler {
             let message = warp::ws::Message::text(json);
 
             if let Err(e) = ws_tx.send(message).await {
-                println!("Error occurred sending to socket {:?}", e);
+                warn!("Error occurred sending to socket {:?}", e);
                 return;
             }
         }
     }
 }
+
+/// Selects the frames sent to a trace, the types are a comma separated list of the
+/// command and notification types to include, every frame is included without them.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct TraceFilter {
+    types: Option<String>,
+}
+
+impl TraceFilter {
+    fn matches(&self, event: &TraceEvent) -> bool {
+        let types = match &self.types {
+            Some(types) => types,
+            None => return true,
+        };
+
+        match event.type_name() {
+            Some(type_name) => types.split(',').any(|r#type| r#type.trim() == type_name),
+            None => false,
+        }
+    }
+}
+
+pub struct GrainfatherTraceHandler {}
+
+impl GrainfatherTraceHandler {
+    /// Sends frames to the socket until either the client disconnects, or the manager
+    /// stops tracing.
+    pub async fn run(gf: GrainfatherManager, filter: TraceFilter, ws: WebSocket) {
+        let (mut ws_tx, mut ws_rx) = ws.split();
+
+        let mut trace_rx = gf.trace();
+
+        loop {
+            let event = tokio::select! {
+                event = trace_rx.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+
+                // Anything the client sends is ignored, but it's watched to notice it leaving
+                message = ws_rx.next() => match message {
+                    Some(Ok(message)) if !message.is_close() => continue,
+                    _ => break,
+                },
+            };
+
+            if !filter.matches(&event) {
+                continue;
+            }
+
+            let message = warp::ws::Message::text(serde_json::to_string(&event).unwrap());
+
+            if let Err(e) = ws_tx.send(message).await {
+                warn!("Error occurred sending trace to socket {:?}", e);
+                break;
+            }
+        }
+
+        let _ = ws_tx.close().await;
+    }
+}
@@ -1,6 +1,7 @@
 use crate::DeviceInfo;
 use bm_db::DB;
 use bm_tilt::{Tilt, TiltColor};
+use bm_units::CentiCelsius;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
@@ -32,7 +33,7 @@ pub fn route(
         async move {
             if let Some(info) = tilts.read().unwrap().get(color.color()) {
                 Ok(warp::reply::json(&TiltStatus {
-                    centi_celsius: ((i32::from(info.device.fahrenheit) - 32) * 500) / 9,
+                    centi_celsius: CentiCelsius::from(info.device.fahrenheit),
                 }))
             } else {
                 Err(warp::reject::not_found())
@@ -79,5 +80,5 @@ impl std::str::FromStr for TiltColorParam {
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct TiltStatus {
-    centi_celsius: i32,
+    centi_celsius: CentiCelsius,
 }
@@ -8,11 +8,15 @@ import { Heat } from "./heat";
 import { Recipe } from "./recipe";
 
 export interface GrainfatherProps {
+    // The alias, or address, of the controller
+    alias: string;
 }
 
 export interface GrainfatherState {
     client: Client;
     ws_url: string;
+    connected: boolean;
+    info: Proto.GrainfatherInfo | null;
 
     status1: Proto.Status1Data;
     status2: Proto.Status2Data;
@@ -29,8 +33,10 @@ export class Grainfather extends React.Component<GrainfatherProps, GrainfatherSt
         super(props);
 
         this.state = {
-            client: new Client(`${window.location.protocol}//${window.location.host}/gf`),
-            ws_url: `ws://${window.location.host}/gf/ws`,
+            client: new Client(`${window.location.protocol}//${window.location.host}/gf/${encodeURIComponent(props.alias)}`),
+            ws_url: `ws://${window.location.host}/gf/${encodeURIComponent(props.alias)}/ws`,
+            connected: false,
+            info: null,
 
             status1: Proto.defaultStatus1(),
             status2: Proto.defaultStatus2(),
@@ -52,7 +58,7 @@ export class Grainfather extends React.Component<GrainfatherProps, GrainfatherSt
                 "name": "STIPA",
                 "hop_stand_time": 0,
                 "boil_power_mode": false,
-                "strike_temp_mode": false,
+                "strike_temperature": null,
                 "boil_steps": [
                     9,
                     6,
@@ -74,6 +80,10 @@ export class Grainfather extends React.Component<GrainfatherProps, GrainfatherSt
         <React.Fragment>
             <div id="bm-overview-panel">
                 <h2 className="bm-overview-panel-header">{this.state.recipe.name}</h2>
+                <p>{this.state.connected ? "Connected" : "Disconnected"}</p>
+                {this.state.info && this.state.info.voltage &&
+                    <p>{this.state.info.voltage.type === "V110" ? "110 V" : "230 V"}, {this.state.info.heat_power_watts} W</p>
+                }
 
                 <Heat
                     client={this.state.client}
@@ -105,6 +115,15 @@ export class Grainfather extends React.Component<GrainfatherProps, GrainfatherSt
         let notification: Proto.Notification = JSON.parse(event.data);
 
         switch (notification.type) {
+            case "Connected":
+                this.setState({...this.state, connected: true});
+                break;
+            case "Disconnected":
+                this.setState({...this.state, connected: false});
+                break;
+            case "Info":
+                this.setState({...this.state, info: notification.data});
+                break;
             case "Status1":
                 this.setState({...this.state, status1: notification.data});
                 break;
@@ -0,0 +1,66 @@
+import * as React from "react";
+import * as Proto from "./types";
+
+import { Grainfather } from "./index";
+
+export interface GrainfatherListProps {
+}
+
+export interface GrainfatherListState {
+    controllers: Proto.ControllerInfo[] | null;
+}
+
+// Shows the only controller, or lists the controllers when there are several
+export class GrainfatherList extends React.Component<GrainfatherListProps, GrainfatherListState> {
+    constructor(props: GrainfatherListProps) {
+        super(props);
+
+        this.state = {
+            controllers: null,
+        };
+
+        this.refreshControllers();
+    }
+
+    render() {
+        let controllers = this.state.controllers;
+
+        if (controllers === null) {
+            return null;
+        }
+
+        if (controllers.length === 1) {
+            return (
+                <Grainfather alias={name(controllers[0])} />
+            );
+        }
+
+        if (controllers.length === 0) {
+            return (
+                <p>No grainfathers have been found yet</p>
+            );
+        }
+
+        return (
+            <ul>
+                {controllers.map(controller => (
+                    <li key={controller.address}>
+                        <a href={`#/gf/${encodeURIComponent(name(controller))}/`}>{name(controller)}</a>
+                        {controller.connected ? " (connected)" : " (disconnected)"}
+                    </li>
+                ))}
+            </ul>
+        );
+    }
+
+    async refreshControllers() {
+        let response = await fetch(`${window.location.protocol}//${window.location.host}/gf`);
+        let controllers: Proto.ControllerInfo[] = await response.json();
+
+        this.setState({...this.state, controllers});
+    }
+}
+
+function name(controller: Proto.ControllerInfo): string {
+    return controller.alias ?? controller.address;
+}
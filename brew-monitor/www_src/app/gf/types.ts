@@ -54,6 +54,11 @@ export type Notification
     | TimerNotification
     | BoilAlertStateNotification
     | HeatSpargeWaterAlertStateNotification
+    | SessionStateNotification
+    | RecipeUploadProgressNotification
+    | ConnectedNotification
+    | DisconnectedNotification
+    | InfoNotification
     ;
 
 export interface Status1Notification {
@@ -126,6 +131,61 @@ export interface HeatSpargeWaterAlertStateData {
     visible: boolean;
 }
 
+interface SessionStateNotification {
+    type: "SessionState";
+    data: SessionStateData;
+}
+
+export interface SessionStateData {
+    phase: BrewPhase | null;
+    active_boil_addition: number | null;
+    remaining_seconds: number | null;
+}
+
+interface ConnectedNotification {
+    type: "Connected";
+}
+
+interface DisconnectedNotification {
+    type: "Disconnected";
+}
+
+interface InfoNotification {
+    type: "Info";
+    data: GrainfatherInfo;
+}
+
+export interface GrainfatherInfo {
+    firmware_version: string | null;
+    voltage: { type: "V110" } | { type: "V230" } | null;
+    units: { type: "Fahrenheit" } | { type: "Celsius" } | null;
+    boil_temperature: number | null;
+    heat_power_watts: number | null;
+}
+
+interface RecipeUploadProgressNotification {
+    type: "RecipeUploadProgress";
+    data: RecipeUploadProgress;
+}
+
+export type RecipeUploadProgress
+    = { type: "Sending", data: { attempt: number, sent: number, total: number } }
+    | { type: "Verifying", data: { attempt: number } }
+    ;
+
+export type BrewPhase
+    = { type: "DelayedStart" }
+    | { type: "HeatingStrike" }
+    | { type: "AddGrain" }
+    | { type: "MashStep", data: number }
+    | { type: "MashOut" }
+    | { type: "Sparge" }
+    | { type: "HeatingToBoil" }
+    | { type: "Boil" }
+    | { type: "HopStand" }
+    | { type: "Finished" }
+    ;
+
 // -----------------------------------------------------------------------------
 // Recipes
 // -----------------------------------------------------------------------------
@@ -158,11 +218,38 @@ export interface Recipe {
     name: string;
     hop_stand_time: number;
     boil_power_mode: boolean;
-    strike_temp_mode: boolean;
+    strike_temperature: number | null;
     boil_steps: number[];
     mash_steps: RecipeMashStep[];
 }
 
+// -----------------------------------------------------------------------------
+// Controllers
+// -----------------------------------------------------------------------------
+export interface ControllerInfo {
+    address: string;
+    alias: string | null;
+    connected: boolean;
+}
+
+// -----------------------------------------------------------------------------
+// Errors
+// -----------------------------------------------------------------------------
+export interface ErrorResponse {
+    message: string;
+    error: GrainfatherError;
+}
+
+export type GrainfatherError
+    = { type: "NotConnected" }
+    | { type: "QueueFull", data: { depth: number } }
+    | { type: "Transport", data: { error: string } }
+    | { type: "Timeout", data: { milliseconds: number } }
+    | { type: "InvalidRecipe", data: { problems: any[] } }
+    | { type: "Encode", data: { error: string } }
+    | { type: "RecipeNotLoaded", data: { attempts: number } }
+    ;
+
 export function defaultStatus1(): Status1Data {
     return {
         heat_active: false,
@@ -1,5 +1,6 @@
 import * as React from "react";
-import { Grainfather } from "./gf/index";
+import { Grainfather, GrainfatherProps } from "./gf/index";
+import { GrainfatherList } from "./gf/list";
 import { Tilt, TiltProps } from "./tilt/index";
 
 import {
@@ -18,13 +19,24 @@ export const App = (_props: AppProps) => (
             <Route path="/tilt/:color/">
                 <TiltWithColor />
             </Route>
+            <Route path="/gf/:alias/">
+                <GrainfatherWithAlias />
+            </Route>
             <Route path="/">
-                <Grainfather />
+                <GrainfatherList />
             </Route>
         </Switch>
     </Router>
 );
 
+function GrainfatherWithAlias(): React.ReactElement {
+    let { alias } = useParams<GrainfatherProps>();
+
+    return (
+        <Grainfather alias={alias} />
+    );
+}
+
 function TiltWithColor(): React.ReactElement {
     let { color } = useParams<TiltProps>();
 
@@ -0,0 +1,223 @@
+//! Compares parsing advertisements in place against the allocating parser it replaced,
+//! which copied every entry's data, on the paths used to detect Tilts and Grainfathers,
+//! and on the advertisements of other devices that both paths reject.
+//!
+//! The allocating parser is compiled into the benchmark, so unlike the library's parser
+//! it can be inlined, which favours it slightly.
+
+use bm_bluetooth::*;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+/// A Tilt's advertisement, flags followed by an Apple beacon.
+const TILT: &[u8] =
+    b"\x02\x01\x06\x1a\xff\x4c\x00\x02\x15\xa4\x95\xbb\x10\xc5\xb1\x4b\x44\xb5\x12\x13\x70\xf0\x2d\x74\xde\x00\x44\x03\xf8\xc5";
+
+/// A Grainfather's advertisement, flags, its name, then its service id in full.
+const GRAINFATHER: &[u8] = b"\x02\x01\x06\x06\tGrain\x11\x07\xfb4\x9b_\x80\0\0\x80\0\x10\0\0\xd0\xcd\0\0";
+
+/// Another device's advertisement, as most of those heard are, which both detection
+/// paths read in full before rejecting.
+const OTHER: &[u8] = b"\x02\x01\x1a\x0c\x09Pixel 7 Pro\x05\x02\x0f\x18\x0a\x18\x1a\xff\x06\x00\x01\x09\x20\x02\x8e\x6d\x2a\x5c\x11\x43\x0b\x9d\x7f\x30\x61\x12\xa4\x58\xe3\x55\x03\xa0\xbd";
+
+const GRAINFATHER_SERVICE_ID: u128 = 0x0000cdd000001000800000805f9b34fb;
+
+/// The parser as it was before it borrowed from the advertisement, kept only to be
+/// compared against.
+mod allocating {
+    use bm_bluetooth::{expand_short_uuid, Beacon};
+    use byteorder::{BigEndian, ByteOrder, LittleEndian};
+    use uuid::Uuid;
+
+    #[allow(dead_code)]
+    pub enum EIREntry {
+        Flags(u8),
+        Name(String),
+        ShortName(String),
+        ServiceIds(Vec<Uuid>),
+        IncompleteServiceIds(Vec<Uuid>),
+        ServiceData(Uuid, Vec<u8>),
+        TxPower(i8),
+        Appearance(u16),
+        ManufacturerSpecific(ManufacturerSpecificEntry),
+        Other(u8, Vec<u8>),
+        Malformed(u8, Vec<u8>),
+    }
+
+    #[allow(dead_code)]
+    pub enum ManufacturerSpecificEntry {
+        Apple(AppleEntry),
+        Other(u16, Vec<u8>),
+    }
+
+    #[allow(dead_code)]
+    pub enum AppleEntry {
+        Beacon(Beacon),
+        Other(u8, Vec<u8>),
+    }
+
+    impl EIREntry {
+        pub fn service_ids(&self) -> &[Uuid] {
+            match self {
+                Self::ServiceIds(ids) | Self::IncompleteServiceIds(ids) => ids,
+                Self::ServiceData(id, _) => std::slice::from_ref(id),
+                _ => &[],
+            }
+        }
+    }
+
+    pub fn parse(mut data: &[u8]) -> impl Iterator<Item = EIREntry> + '_ {
+        std::iter::from_fn(move || {
+            let entry_length = usize::from(*data.first()?);
+
+            if entry_length == 0 || entry_length + 1 > data.len() {
+                return None;
+            }
+
+            let entry = &data[1..=entry_length];
+            data = &data[entry_length + 1..];
+
+            Some(parse_entry(entry[0], &entry[1..]))
+        })
+    }
+
+    fn parse_entry(entry_type: u8, data: &[u8]) -> EIREntry {
+        let entry = match entry_type {
+            0x01 => data.first().map(|flags| EIREntry::Flags(*flags)),
+            0x02 => parse_uuids(data, 2).map(EIREntry::IncompleteServiceIds),
+            0x03 => parse_uuids(data, 2).map(EIREntry::ServiceIds),
+            0x04 => parse_uuids(data, 4).map(EIREntry::IncompleteServiceIds),
+            0x05 => parse_uuids(data, 4).map(EIREntry::ServiceIds),
+            0x06 => parse_uuids(data, 16).map(EIREntry::IncompleteServiceIds),
+            0x07 => parse_uuids(data, 16).map(EIREntry::ServiceIds),
+            0x08 => Some(EIREntry::ShortName(String::from_utf8_lossy(data).into())),
+            0x09 => Some(EIREntry::Name(String::from_utf8_lossy(data).into())),
+            0x0A => match data {
+                [power] => Some(EIREntry::TxPower(*power as i8)),
+                _ => None,
+            },
+            0x16 => parse_service_data(data, 2),
+            0x20 => parse_service_data(data, 4),
+            0x21 => parse_service_data(data, 16),
+            0x19 if data.len() == 2 => Some(EIREntry::Appearance(LittleEndian::read_u16(data))),
+            0xFF => parse_manufacturer_specific(data).map(EIREntry::ManufacturerSpecific),
+            _ => Some(EIREntry::Other(entry_type, data.to_vec())),
+        };
+
+        entry.unwrap_or_else(|| EIREntry::Malformed(entry_type, data.to_vec()))
+    }
+
+    fn parse_service_data(data: &[u8], uuid_size: usize) -> Option<EIREntry> {
+        if data.len() < uuid_size {
+            return None;
+        }
+
+        let (uuid, service_data) = data.split_at(uuid_size);
+
+        Some(EIREntry::ServiceData(read_uuid(uuid), service_data.to_vec()))
+    }
+
+    fn parse_manufacturer_specific(data: &[u8]) -> Option<ManufacturerSpecificEntry> {
+        if data.len() < 2 {
+            return None;
+        }
+
+        let manufacturer = LittleEndian::read_u16(&data[0..2]);
+
+        match (manufacturer, &data[2..]) {
+            (0x004c, [0x02, 21, beacon @ ..]) if beacon.len() >= 21 => {
+                Some(ManufacturerSpecificEntry::Apple(AppleEntry::Beacon(Beacon {
+                    uuid: Uuid::from_u128(BigEndian::read_u128(&beacon[0..16])),
+                    major: BigEndian::read_u16(&beacon[16..18]),
+                    minor: BigEndian::read_u16(&beacon[18..20]),
+                    power: beacon[20] as i8,
+                })))
+            }
+
+            (0x004c, [0x02, ..]) | (0x004c, []) => None,
+            (0x004c, [entry_type, rest @ ..]) => {
+                Some(ManufacturerSpecificEntry::Apple(AppleEntry::Other(*entry_type, rest.to_vec())))
+            }
+
+            (_, specific_data) => Some(ManufacturerSpecificEntry::Other(manufacturer, specific_data.to_vec())),
+        }
+    }
+
+    fn parse_uuids(data: &[u8], uuid_size: usize) -> Option<Vec<Uuid>> {
+        let chunks = data.chunks_exact(uuid_size);
+
+        if !chunks.remainder().is_empty() {
+            return None;
+        }
+
+        Some(chunks.map(read_uuid).collect())
+    }
+
+    fn read_uuid(data: &[u8]) -> Uuid {
+        match data.len() {
+            2 => expand_short_uuid(u32::from(LittleEndian::read_u16(data))),
+            4 => expand_short_uuid(LittleEndian::read_u32(data)),
+            _ => Uuid::from_u128(LittleEndian::read_u128(data)),
+        }
+    }
+}
+
+fn find_beacon(report: &EIRData) -> Option<Beacon> {
+    report.into_iter().find_map(|entry| match entry {
+        EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Apple(AppleEntry::Beacon(beacon))) => Some(beacon),
+        _ => None,
+    })
+}
+
+fn find_beacon_allocating(data: &[u8]) -> Option<Beacon> {
+    use allocating::*;
+
+    parse(data).find_map(|entry| match entry {
+        EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Apple(AppleEntry::Beacon(beacon))) => Some(beacon),
+        _ => None,
+    })
+}
+
+fn has_service_id(report: &EIRData) -> bool {
+    report.into_iter().any(|entry| entry.service_ids().any(|id| id.as_u128() == GRAINFATHER_SERVICE_ID))
+}
+
+fn has_service_id_allocating(data: &[u8]) -> bool {
+    allocating::parse(data).any(|entry| entry.service_ids().iter().any(|id| id.as_u128() == GRAINFATHER_SERVICE_ID))
+}
+
+fn tilt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tilt");
+
+    group.bench_function("borrowed", |b| b.iter(|| find_beacon(&EIRData::from(black_box(TILT)))));
+    group.bench_function("allocating", |b| b.iter(|| find_beacon_allocating(black_box(TILT))));
+
+    group.finish();
+}
+
+fn grainfather(c: &mut Criterion) {
+    let mut group = c.benchmark_group("grainfather");
+
+    group.bench_function("borrowed", |b| b.iter(|| has_service_id(&EIRData::from(black_box(GRAINFATHER)))));
+    group.bench_function("allocating", |b| b.iter(|| has_service_id_allocating(black_box(GRAINFATHER))));
+
+    group.finish();
+}
+
+fn other(c: &mut Criterion) {
+    let mut group = c.benchmark_group("other");
+
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let report = EIRData::from(black_box(OTHER));
+            (find_beacon(&report), has_service_id(&report))
+        })
+    });
+    group.bench_function("allocating", |b| {
+        b.iter(|| (find_beacon_allocating(black_box(OTHER)), has_service_id_allocating(black_box(OTHER))))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, tilt, grainfather, other);
+criterion_main!(benches);
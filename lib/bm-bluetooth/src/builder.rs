@@ -0,0 +1,361 @@
+use crate::*;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::borrow::Cow;
+use uuid::Uuid;
+
+/// The most data an entry can hold, its single byte length also counts the type.
+const MAX_ENTRY_DATA_SIZE: usize = 254;
+
+#[derive(Debug, PartialEq)]
+pub enum EIREncodeError {
+    /// The entry with the given type had more data than fits in a single entry.
+    TooLong(u8, usize),
+}
+
+/// Builds EIR or advertising data from its entries, in the order they're given.
+///
+/// ```
+/// # use bm_bluetooth::*;
+/// let advertisement = EIRBuilder::new().with_flags(0x06).with_name("Grain").build().unwrap();
+///
+/// assert_eq!(b"\x02\x01\x06\x06\x09Grain", advertisement.as_slice());
+/// ```
+#[derive(Debug, Default)]
+pub struct EIRBuilder {
+    entries: Vec<EIREntry<'static>>,
+}
+
+impl EIRBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_entry(mut self, entry: EIREntry<'_>) -> Self {
+        self.entries.push(entry.into_owned());
+        self
+    }
+
+    pub fn with_flags(self, flags: u8) -> Self {
+        self.with_entry(EIREntry::Flags(flags))
+    }
+
+    pub fn with_name(self, name: &str) -> Self {
+        self.with_entry(EIREntry::Name(name.into()))
+    }
+
+    pub fn with_short_name(self, name: &str) -> Self {
+        self.with_entry(EIREntry::ShortName(name.into()))
+    }
+
+    /// Adds a complete list of the services offered, advertised in the smallest size
+    /// that fits them all.
+    pub fn with_service_ids(self, ids: &[Uuid]) -> Self {
+        self.with_entry(EIREntry::ServiceIds(Uuids::from_ids(ids)))
+    }
+
+    /// Adds a list of some of the services offered, advertised in the smallest size
+    /// that fits them all.
+    pub fn with_incomplete_service_ids(self, ids: &[Uuid]) -> Self {
+        self.with_entry(EIREntry::IncompleteServiceIds(Uuids::from_ids(ids)))
+    }
+
+    pub fn with_manufacturer_data(self, manufacturer: u16, data: &[u8]) -> Self {
+        self.with_entry(EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Other(manufacturer, data.into())))
+    }
+
+    /// Adds an Apple iBeacon, as advertised by a Tilt.
+    pub fn with_beacon(self, beacon: Beacon) -> Self {
+        self.with_entry(EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Apple(AppleEntry::Beacon(beacon))))
+    }
+
+    pub fn build(&self) -> Result<Vec<u8>, EIREncodeError> {
+        let mut data = Vec::new();
+
+        for entry in &self.entries {
+            data.extend(entry.to_vec()?);
+        }
+
+        Ok(data)
+    }
+}
+
+impl EIREntry<'_> {
+    /// Encodes the entry, including its length and type, failing if its data doesn't
+    /// fit in a single entry.
+    pub fn to_vec(&self) -> Result<Vec<u8>, EIREncodeError> {
+        let mut data = Vec::new();
+
+        let entry_type = match self {
+            Self::Flags(flags) => {
+                data.push(*flags);
+                0x01
+            }
+
+            Self::ShortName(name) => {
+                data.extend_from_slice(name.as_bytes());
+                0x08
+            }
+
+            Self::Name(name) => {
+                data.extend_from_slice(name.as_bytes());
+                0x09
+            }
+
+            Self::IncompleteServiceIds(ids) => {
+                data.extend_from_slice(&ids.data);
+
+                match ids.uuid_size {
+                    2 => 0x02,
+                    4 => 0x04,
+                    _ => 0x06,
+                }
+            }
+
+            Self::ServiceIds(ids) => {
+                data.extend_from_slice(&ids.data);
+
+                match ids.uuid_size {
+                    2 => 0x03,
+                    4 => 0x05,
+                    _ => 0x07,
+                }
+            }
+
+            Self::ServiceData(id, service_data) => {
+                let uuid_size = write_uuid(&mut data, id);
+                data.extend_from_slice(service_data);
+
+                match uuid_size {
+                    2 => 0x16,
+                    4 => 0x20,
+                    _ => 0x21,
+                }
+            }
+
+            Self::TxPower(power) => {
+                data.push(*power as u8);
+                0x0A
+            }
+
+            Self::Appearance(appearance) => {
+                data.extend_from_slice(&appearance.to_le_bytes());
+                0x19
+            }
+
+            Self::ManufacturerSpecific(entry) => {
+                entry.write(&mut data);
+                0xFF
+            }
+
+            Self::Other(entry_type, other) | Self::Malformed(entry_type, other) => {
+                data.extend_from_slice(other);
+                *entry_type
+            }
+        };
+
+        if data.len() > MAX_ENTRY_DATA_SIZE {
+            return Err(EIREncodeError::TooLong(entry_type, data.len()));
+        }
+
+        Ok([&[data.len() as u8 + 1, entry_type][..], &data].concat())
+    }
+}
+
+impl ManufacturerSpecificEntry<'_> {
+    fn write(&self, data: &mut Vec<u8>) {
+        match self {
+            Self::Apple(AppleEntry::Beacon(beacon)) => {
+                data.extend_from_slice(&0x004cu16.to_le_bytes());
+                data.push(0x02);
+                beacon.write(data);
+            }
+
+            Self::Apple(AppleEntry::Other(entry_type, other)) => {
+                data.extend_from_slice(&0x004cu16.to_le_bytes());
+                data.push(*entry_type);
+                data.extend_from_slice(other);
+            }
+
+            Self::Other(manufacturer, other) => {
+                data.extend_from_slice(&manufacturer.to_le_bytes());
+                data.extend_from_slice(other);
+            }
+        }
+    }
+}
+
+impl Beacon {
+    fn write(&self, data: &mut Vec<u8>) {
+        let mut beacon = [0; Self::SIZE];
+
+        BigEndian::write_u128(&mut beacon[0..16], self.uuid.as_u128());
+        BigEndian::write_u16(&mut beacon[16..18], self.major);
+        BigEndian::write_u16(&mut beacon[18..20], self.minor);
+        beacon[20] = self.power as u8;
+
+        data.push(Self::SIZE as u8);
+        data.extend_from_slice(&beacon);
+    }
+}
+
+impl Uuids<'static> {
+    /// Lists the given UUIDs in the smallest size that fits them all.
+    pub fn from_ids(ids: &[Uuid]) -> Self {
+        let short_ids = ids.iter().map(shorten_uuid).collect::<Option<Vec<_>>>();
+
+        let uuid_size = match short_ids.as_ref().and_then(|short| short.iter().max()) {
+            Some(max) if *max <= 0xffff => 2,
+            Some(_) => 4,
+            None if ids.is_empty() => 2,
+            None => 16,
+        };
+
+        let mut data = Vec::with_capacity(ids.len() * uuid_size);
+
+        for id in ids {
+            write_uuid_of_size(&mut data, id, uuid_size);
+        }
+
+        Self {
+            data: Cow::Owned(data),
+            uuid_size,
+        }
+    }
+}
+
+/// The 16 or 32-bit UUID the given UUID is shorthand for, if it's based on the
+/// [Bluetooth base UUID](crate::BLUETOOTH_BASE_UUID).
+fn shorten_uuid(uuid: &Uuid) -> Option<u32> {
+    let value = uuid.as_u128();
+
+    if value & !(u128::from(u32::MAX) << 96) == BLUETOOTH_BASE_UUID {
+        Some((value >> 96) as u32)
+    } else {
+        None
+    }
+}
+
+/// Writes the given UUID in the smallest size it fits in, giving the size written.
+fn write_uuid(data: &mut Vec<u8>, uuid: &Uuid) -> usize {
+    let uuid_size = match shorten_uuid(uuid) {
+        Some(short) if short <= 0xffff => 2,
+        Some(_) => 4,
+        None => 16,
+    };
+
+    write_uuid_of_size(data, uuid, uuid_size);
+    uuid_size
+}
+
+/// Writes a UUID as a little endian 16, 32, or 128-bit UUID, which it must fit in.
+fn write_uuid_of_size(data: &mut Vec<u8>, uuid: &Uuid, uuid_size: usize) {
+    let value = uuid.as_u128();
+
+    match uuid_size {
+        2 => data.extend_from_slice(&((value >> 96) as u16).to_le_bytes()),
+        4 => data.extend_from_slice(&((value >> 96) as u32).to_le_bytes()),
+        _ => {
+            let mut full = [0; 16];
+            LittleEndian::write_u128(&mut full, value);
+            data.extend_from_slice(&full);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn parse(data: &[u8]) -> Vec<EIREntry<'_>> {
+        EIRData::from(data).into_iter().collect()
+    }
+
+    #[test]
+    fn builds_beacons() {
+        let beacon = Beacon {
+            uuid: Uuid::from_u128(0xa495bb10c5b14b44b5121370f02d74de),
+            major: 0x0044,
+            minor: 0x03f8,
+            power: -59,
+        };
+
+        let data = EIRBuilder::new().with_beacon(beacon).build().unwrap();
+
+        assert_eq!(
+            &b"\x1a\xff\x4c\x00\x02\x15\xa4\x95\xbb\x10\xc5\xb1\x4b\x44\xb5\x12\x13\x70\xf0\x2d\x74\xde\x00\x44\x03\xf8\xc5"[..],
+            data.as_slice()
+        );
+    }
+
+    #[test]
+    fn advertises_service_ids_in_the_smallest_size() {
+        let entry_type = |ids: &[Uuid]| EIRBuilder::new().with_service_ids(ids).build().unwrap()[1];
+
+        let short = expand_short_uuid(0xcdd0);
+        let long = expand_short_uuid(0x0102_0304);
+        let full = Uuid::from_u128(0x0003cdd100001000800000805f9b0131);
+
+        assert_eq!(0x03, entry_type(&[short]));
+        assert_eq!(0x05, entry_type(&[short, long]));
+        assert_eq!(0x07, entry_type(&[short, full]));
+
+        assert_eq!(b"\x03\x03\xd0\xcd", EIRBuilder::new().with_service_ids(&[short]).build().unwrap().as_slice());
+    }
+
+    #[test]
+    fn rejects_entries_that_are_too_long() {
+        let name = "a".repeat(MAX_ENTRY_DATA_SIZE + 1);
+
+        assert_eq!(Err(EIREncodeError::TooLong(0x09, 255)), EIRBuilder::new().with_name(&name).build());
+        assert!(EIRBuilder::new().with_name(&name[1..]).build().is_ok());
+    }
+
+    fn entry() -> impl Strategy<Value = EIREntry<'static>> {
+        let bytes = || prop::collection::vec(any::<u8>(), 0..20);
+        let uuid = || {
+            prop_oneof![
+                any::<u16>().prop_map(|short| expand_short_uuid(u32::from(short))),
+                any::<u32>().prop_map(expand_short_uuid),
+                any::<u128>().prop_map(Uuid::from_u128),
+            ]
+        };
+
+        prop_oneof![
+            any::<u8>().prop_map(EIREntry::Flags),
+            "[a-zA-Z0-9 ]{0,20}".prop_map(|name| EIREntry::Name(name.into())),
+            "[a-zA-Z0-9 ]{0,20}".prop_map(|name| EIREntry::ShortName(name.into())),
+            prop::collection::vec(uuid(), 0..4).prop_map(|ids| EIREntry::ServiceIds(Uuids::from_ids(&ids))),
+            prop::collection::vec(uuid(), 0..4).prop_map(|ids| EIREntry::IncompleteServiceIds(Uuids::from_ids(&ids))),
+            (uuid(), bytes()).prop_map(|(id, data)| EIREntry::ServiceData(id, data.into())),
+            any::<i8>().prop_map(EIREntry::TxPower),
+            any::<u16>().prop_map(EIREntry::Appearance),
+            (any::<[u8; 16]>(), any::<u16>(), any::<u16>(), any::<i8>()).prop_map(|(uuid, major, minor, power)| {
+                EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Apple(AppleEntry::Beacon(Beacon {
+                    uuid: Uuid::from_bytes(uuid),
+                    major,
+                    minor,
+                    power,
+                })))
+            }),
+            (any::<u16>().prop_filter("Apple entries are parsed", |id| *id != 0x004c), bytes()).prop_map(
+                |(manufacturer, data)| {
+                    EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Other(manufacturer, data.into()))
+                }
+            ),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_entries(entries in prop::collection::vec(entry(), 0..8)) {
+            let data = entries
+                .iter()
+                .fold(EIRBuilder::new(), |builder, entry| builder.with_entry(entry.clone()))
+                .build()
+                .unwrap();
+
+            prop_assert_eq!(entries, parse(&data));
+        }
+    }
+}
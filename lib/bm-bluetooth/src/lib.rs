@@ -1,8 +1,21 @@
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::borrow::Cow;
 use uuid::Uuid;
 
+mod builder;
+pub use builder::*;
+
 const EIR_HEADER_SIZE: usize = 2;
 
+/// The UUID that 16 and 32-bit UUIDs assigned by the Bluetooth SIG are shorthand for, the
+/// short UUID takes the place of the most significant 32 bits.
+pub const BLUETOOTH_BASE_UUID: u128 = 0x00000000_0000_1000_8000_00805f9b34fb;
+
+/// Expands a 16 or 32-bit UUID against the [Bluetooth base UUID](crate::BLUETOOTH_BASE_UUID).
+pub fn expand_short_uuid(short: u32) -> Uuid {
+    Uuid::from_u128(BLUETOOTH_BASE_UUID | (u128::from(short) << 96))
+}
+
 pub struct EIRData<'a>(&'a [u8]);
 
 impl<'a> From<&'a [u8]> for EIRData<'a> {
@@ -12,7 +25,7 @@ impl<'a> From<&'a [u8]> for EIRData<'a> {
 }
 
 impl<'a> IntoIterator for &EIRData<'a> {
-    type Item = EIREntry;
+    type Item = EIREntry<'a>;
     type IntoIter = EIRDataIter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
@@ -20,10 +33,12 @@ impl<'a> IntoIterator for &EIRData<'a> {
     }
 }
 
+/// Iterates over the entries in EIR data, stopping at the first zero length entry, which
+/// marks the end of the significant data, or at an entry that runs past the end of the data.
 pub struct EIRDataIter<'a>(&'a [u8]);
 
-impl Iterator for EIRDataIter<'_> {
-    type Item = EIREntry;
+impl<'a> Iterator for EIRDataIter<'a> {
+    type Item = EIREntry<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.0.len() < EIR_HEADER_SIZE {
@@ -35,6 +50,11 @@ impl Iterator for EIRDataIter<'_> {
         let index_type = 1;
         let index_next = index_type + entry_length;
 
+        if entry_length == 0 || index_next > self.0.len() {
+            self.0 = &[];
+            return None;
+        }
+
         let this_entry = &self.0[index_type..index_next];
 
         self.0 = &self.0[index_next..];
@@ -43,73 +63,246 @@ impl Iterator for EIRDataIter<'_> {
     }
 }
 
-#[derive(Debug)]
-pub enum EIREntry {
+/// An entry in EIR or advertising data, borrowing from the data it was parsed from, so that
+/// parsing doesn't allocate, use [`into_owned`](EIREntry::into_owned) to keep it for longer.
+///
+/// Service UUIDs are always given in full, however they were advertised, so that they can be
+/// compared regardless of their advertised size.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EIREntry<'a> {
     Flags(u8),
-    Name(String),
-    ServiceIds(Vec<Uuid>),
-    ManufacturerSpecific(ManufacturerSpecificEntry),
-    Other(u8, Vec<u8>),
+
+    /// The complete local name.
+    Name(Cow<'a, str>),
+    ShortName(Cow<'a, str>),
+
+    /// A complete list of the services offered, of any size.
+    ServiceIds(Uuids<'a>),
+
+    /// A list of some of the services offered, of any size.
+    IncompleteServiceIds(Uuids<'a>),
+
+    /// The data associated with the given service.
+    ServiceData(Uuid, Cow<'a, [u8]>),
+
+    /// The transmitted power level in dBm.
+    TxPower(i8),
+
+    /// The external appearance of the device, as assigned by the Bluetooth SIG.
+    Appearance(u16),
+
+    ManufacturerSpecific(ManufacturerSpecificEntry<'a>),
+    Other(u8, Cow<'a, [u8]>),
+
+    /// An entry of a known type whose data doesn't have the expected shape, e.g. it's
+    /// too short, the type and data are kept as they were received.
+    Malformed(u8, Cow<'a, [u8]>),
 }
 
-impl EIREntry {
-    fn parse(eir_data: &[u8]) -> Self {
+impl<'a> EIREntry<'a> {
+    /// Parses an entry, which must contain at least the type.
+    fn parse(eir_data: &'a [u8]) -> Self {
         let entry_type = eir_data[0];
         let entry_data = &eir_data[1..];
 
-        match entry_type {
-            0x01 => Self::Flags(entry_data[0]),
+        let entry = match entry_type {
+            0x01 => entry_data.first().map(|flags| Self::Flags(*flags)),
+
+            0x02 => Uuids::parse(entry_data, 2).map(Self::IncompleteServiceIds),
+            0x03 => Uuids::parse(entry_data, 2).map(Self::ServiceIds),
+            0x04 => Uuids::parse(entry_data, 4).map(Self::IncompleteServiceIds),
+            0x05 => Uuids::parse(entry_data, 4).map(Self::ServiceIds),
+            0x06 => Uuids::parse(entry_data, 16).map(Self::IncompleteServiceIds),
+            0x07 => Uuids::parse(entry_data, 16).map(Self::ServiceIds),
+
+            0x08 => Some(Self::ShortName(String::from_utf8_lossy(entry_data))),
+            0x09 => Some(Self::Name(String::from_utf8_lossy(entry_data))),
+
+            0x0A => match entry_data {
+                [power] => Some(Self::TxPower(*power as i8)),
+                _ => None,
+            },
+
+            0x16 => Self::parse_service_data(entry_data, 2),
+            0x20 => Self::parse_service_data(entry_data, 4),
+            0x21 => Self::parse_service_data(entry_data, 16),
+
+            0x19 => match entry_data {
+                [_, _] => Some(Self::Appearance(LittleEndian::read_u16(entry_data))),
+                _ => None,
+            },
 
-            0x07 => Self::ServiceIds(
-                entry_data.chunks(16).map(LittleEndian::read_u128).map(Uuid::from_u128).collect::<Vec<_>>(),
-            ),
+            0xFF => ManufacturerSpecificEntry::parse(entry_data).map(Self::ManufacturerSpecific),
 
-            0x09 => Self::Name(String::from_utf8_lossy(entry_data).into()),
+            _ => Some(Self::Other(entry_type, entry_data.into())),
+        };
 
-            0xFF => Self::ManufacturerSpecific(ManufacturerSpecificEntry::parse(entry_data)),
+        entry.unwrap_or_else(|| Self::Malformed(entry_type, entry_data.into()))
+    }
 
-            _ => Self::Other(entry_type, entry_data.to_vec()),
+    fn parse_service_data(data: &'a [u8], uuid_size: usize) -> Option<Self> {
+        if data.len() < uuid_size {
+            return None;
+        }
+
+        let (uuid, service_data) = data.split_at(uuid_size);
+
+        Some(Self::ServiceData(read_uuid(uuid), service_data.into()))
+    }
+
+    /// The services the entry refers to, whether it lists services, or carries the data
+    /// of a service.
+    pub fn service_ids(&self) -> impl Iterator<Item = Uuid> + '_ {
+        let (ids, id) = match self {
+            Self::ServiceIds(ids) | Self::IncompleteServiceIds(ids) => (Some(ids.iter()), None),
+            Self::ServiceData(id, _) => (None, Some(*id)),
+            _ => (None, None),
+        };
+
+        ids.into_iter().flatten().chain(id)
+    }
+
+    /// Copies any data borrowed from the advertisement, so that the entry can outlive it.
+    pub fn into_owned(self) -> EIREntry<'static> {
+        match self {
+            Self::Flags(flags) => EIREntry::Flags(flags),
+            Self::Name(name) => EIREntry::Name(Cow::Owned(name.into_owned())),
+            Self::ShortName(name) => EIREntry::ShortName(Cow::Owned(name.into_owned())),
+            Self::ServiceIds(ids) => EIREntry::ServiceIds(ids.into_owned()),
+            Self::IncompleteServiceIds(ids) => EIREntry::IncompleteServiceIds(ids.into_owned()),
+            Self::ServiceData(id, data) => EIREntry::ServiceData(id, Cow::Owned(data.into_owned())),
+            Self::TxPower(power) => EIREntry::TxPower(power),
+            Self::Appearance(appearance) => EIREntry::Appearance(appearance),
+            Self::ManufacturerSpecific(entry) => EIREntry::ManufacturerSpecific(entry.into_owned()),
+            Self::Other(entry_type, data) => EIREntry::Other(entry_type, Cow::Owned(data.into_owned())),
+            Self::Malformed(entry_type, data) => EIREntry::Malformed(entry_type, Cow::Owned(data.into_owned())),
         }
     }
 }
 
-#[derive(Debug)]
-pub enum ManufacturerSpecificEntry {
-    Apple(AppleEntry),
-    Other(u16, Vec<u8>),
+/// A list of service UUIDs of a single size, as advertised, the UUIDs are only expanded
+/// as they're iterated over. Lists compare equal when they hold the same UUIDs, whatever
+/// their advertised size.
+#[derive(Clone)]
+pub struct Uuids<'a> {
+    data: Cow<'a, [u8]>,
+    uuid_size: usize,
 }
 
-impl ManufacturerSpecificEntry {
-    fn parse(data: &[u8]) -> Self {
+impl<'a> Uuids<'a> {
+    /// Views a list of UUIDs of the given size, failing if the list doesn't divide evenly.
+    fn parse(data: &'a [u8], uuid_size: usize) -> Option<Self> {
+        if !data.chunks_exact(uuid_size).remainder().is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            data: data.into(),
+            uuid_size,
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Uuid> + '_ {
+        self.data.chunks(self.uuid_size).map(read_uuid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len() / self.uuid_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn contains(&self, uuid: &Uuid) -> bool {
+        self.iter().any(|id| id == *uuid)
+    }
+
+    pub fn into_owned(self) -> Uuids<'static> {
+        Uuids {
+            data: Cow::Owned(self.data.into_owned()),
+            uuid_size: self.uuid_size,
+        }
+    }
+}
+
+impl PartialEq for Uuids<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl std::fmt::Debug for Uuids<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Reads a little endian 16, 32, or 128-bit UUID.
+fn read_uuid(data: &[u8]) -> Uuid {
+    match data.len() {
+        2 => expand_short_uuid(u32::from(LittleEndian::read_u16(data))),
+        4 => expand_short_uuid(LittleEndian::read_u32(data)),
+        _ => Uuid::from_u128(LittleEndian::read_u128(data)),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ManufacturerSpecificEntry<'a> {
+    Apple(AppleEntry<'a>),
+    Other(u16, Cow<'a, [u8]>),
+}
+
+impl<'a> ManufacturerSpecificEntry<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        if data.len() < 2 {
+            return None;
+        }
+
         let manufacturer = LittleEndian::read_u16(&data[0..2]);
         let specific_data = &data[2..];
 
         match manufacturer {
-            0x004c => Self::Apple(AppleEntry::parse(specific_data)),
-            _ => Self::Other(manufacturer, specific_data.to_vec()),
+            0x004c => AppleEntry::parse(specific_data).map(Self::Apple),
+            _ => Some(Self::Other(manufacturer, specific_data.into())),
+        }
+    }
+
+    pub fn into_owned(self) -> ManufacturerSpecificEntry<'static> {
+        match self {
+            Self::Apple(entry) => ManufacturerSpecificEntry::Apple(entry.into_owned()),
+            Self::Other(manufacturer, data) => {
+                ManufacturerSpecificEntry::Other(manufacturer, Cow::Owned(data.into_owned()))
+            }
         }
     }
 }
 
-#[derive(Debug)]
-pub enum AppleEntry {
+#[derive(Clone, Debug, PartialEq)]
+pub enum AppleEntry<'a> {
     Beacon(Beacon),
-    Other(u8, Vec<u8>),
+    Other(u8, Cow<'a, [u8]>),
 }
 
-impl AppleEntry {
-    fn parse(data: &[u8]) -> Self {
-        let entry_type = data[0];
-        let rest = &data[1..];
+impl<'a> AppleEntry<'a> {
+    fn parse(data: &'a [u8]) -> Option<Self> {
+        let (entry_type, rest) = data.split_first()?;
 
         match entry_type {
-            0x02 => Self::Beacon(Beacon::parse(rest)),
-            _ => Self::Other(entry_type, rest.to_vec()),
+            0x02 => Beacon::parse(rest).map(Self::Beacon),
+            _ => Some(Self::Other(*entry_type, rest.into())),
+        }
+    }
+
+    pub fn into_owned(self) -> AppleEntry<'static> {
+        match self {
+            Self::Beacon(beacon) => AppleEntry::Beacon(beacon),
+            Self::Other(entry_type, data) => AppleEntry::Other(entry_type, Cow::Owned(data.into_owned())),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Beacon {
     pub uuid: Uuid,
     pub minor: u16,
@@ -118,34 +311,184 @@ pub struct Beacon {
 }
 
 impl Beacon {
-    fn parse(data: &[u8]) -> Self {
-        let size = data[0];
-        assert_eq!(21, size);
+    /// The size of a beacon, excluding the size itself.
+    const SIZE: usize = 21;
 
-        let beacon_data = &data[1..];
+    fn parse(data: &[u8]) -> Option<Self> {
+        let (size, beacon_data) = data.split_first()?;
+
+        if *size as usize != Self::SIZE || beacon_data.len() < Self::SIZE {
+            return None;
+        }
 
         let uuid = BigEndian::read_u128(&beacon_data[0..16]);
         let major = BigEndian::read_u16(&beacon_data[16..18]);
         let minor = BigEndian::read_u16(&beacon_data[18..20]);
         let power = beacon_data[20] as i8;
 
-        Self {
+        Some(Self {
             uuid: Uuid::from_u128(uuid),
             major,
             minor,
             power,
-        }
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     #[test]
     pub fn parse_eir_test() {
-        let example_data = b"\x02\x01\x06\x06\tGrain\x11\x07\xfb4\x9b_\x80\0\0\x80\0\x10\0\0\xd0\xcd\0\0";
+        let grainfather = Uuid::from_u128(0x0000cdd0_0000_1000_8000_00805f9b34fb);
+        let example_data = EIRBuilder::new()
+            .with_flags(0x06)
+            .with_name("Grain")
+            .with_incomplete_service_ids(&[grainfather])
+            .build()
+            .unwrap();
+
         let eir_entries = EIRData::from(&example_data[..]).into_iter().collect::<Vec<_>>();
-        println!("Entries: {:?}", eir_entries);
+
+        match eir_entries.as_slice() {
+            [EIREntry::Flags(0x06), EIREntry::Name(name), EIREntry::IncompleteServiceIds(ids)] => {
+                assert_eq!("Grain", name);
+                assert_eq!(vec![grainfather], ids.iter().collect::<Vec<_>>());
+            }
+
+            other => panic!("Unexpected entries {:?}", other),
+        }
+    }
+
+    /// An Apple beacon entry, as advertised by a Tilt.
+    const BEACON: &[u8] =
+        b"\x1a\xff\x4c\x00\x02\x15\xa4\x95\xbb\x10\xc5\xb1\x4b\x44\xb5\x12\x13\x70\xf0\x2d\x74\xde\x00\x44\x03\xf8\xc5";
+
+    fn parse(data: &[u8]) -> Vec<EIREntry<'_>> {
+        EIRData::from(data).into_iter().collect()
+    }
+
+    /// A list holding the given UUID, as advertised in full.
+    fn uuids(uuid: u128) -> Uuids<'static> {
+        Uuids {
+            data: uuid.to_le_bytes().to_vec().into(),
+            uuid_size: 16,
+        }
+    }
+
+    #[test]
+    fn parses_beacons() {
+        match parse(BEACON).as_slice() {
+            [EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Apple(AppleEntry::Beacon(beacon)))] => {
+                assert_eq!(0x0044, beacon.major);
+                assert_eq!(0x03f8, beacon.minor);
+                assert_eq!(-59, beacon.power);
+            }
+
+            other => panic!("Unexpected entries {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_advertising_data_types() {
+        let data =
+            b"\x03\x03\xd0\xcd\x05\x04\x01\x02\x03\x04\x04\x08Gra\x02\x0a\xf4\x03\x19\x40\x02\x05\x16\x0f\x18\x64\x01";
+
+        assert_eq!(
+            vec![
+                EIREntry::ServiceIds(uuids(0x0000cdd0_0000_1000_8000_00805f9b34fb)),
+                EIREntry::IncompleteServiceIds(uuids(0x04030201_0000_1000_8000_00805f9b34fb)),
+                EIREntry::ShortName("Gra".into()),
+                EIREntry::TxPower(-12),
+                EIREntry::Appearance(0x0240),
+                EIREntry::ServiceData(expand_short_uuid(0x180f), vec![0x64, 0x01].into()),
+            ],
+            parse(data)
+        );
+
+        // Any form of service UUID can be matched
+        let incomplete =
+            [b"\x11\x06".to_vec(), 0x0000cdd0_0000_1000_8000_00805f9b34fbu128.to_le_bytes().to_vec()].concat();
+
+        assert_eq!(
+            parse(data)[0].service_ids().collect::<Vec<_>>(),
+            parse(&incomplete)[0].service_ids().collect::<Vec<_>>()
+        );
+        assert_eq!(vec![expand_short_uuid(0x180f)], parse(data)[5].service_ids().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reports_malformed_entries() {
+        // A flags entry without any flags, a truncated service id, a manufacturer without
+        // an id, and a beacon that's too short
+        let data = b"\x01\x01\x03\x07\x01\x02\x02\xff\x01\x06\xff\x4c\x00\x02\x15\x00\x02\x01\x06";
+
+        assert_eq!(
+            vec![
+                EIREntry::Malformed(0x01, vec![].into()),
+                EIREntry::Malformed(0x07, vec![0x01, 0x02].into()),
+                EIREntry::Malformed(0xff, vec![0x01].into()),
+                EIREntry::Malformed(0xff, vec![0x4c, 0x00, 0x02, 0x15, 0x00].into()),
+                EIREntry::Flags(0x06),
+            ],
+            parse(data)
+        );
+    }
+
+    #[test]
+    fn borrows_from_the_advertisement() {
+        let data = b"\x04\x09Gra\x03\xff\x01\x02";
+        let entries = parse(data);
+
+        assert!(matches!(&entries[0], EIREntry::Name(Cow::Borrowed("Gra"))));
+        assert!(matches!(
+            &entries[1],
+            EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Other(0x0201, Cow::Borrowed([])))
+        ));
+
+        let owned = entries.into_iter().map(EIREntry::into_owned).collect::<Vec<_>>();
+
+        assert_eq!(parse(data), owned);
+        assert!(matches!(&owned[0], EIREntry::Name(Cow::Owned(_))));
+    }
+
+    #[test]
+    fn stops_at_truncated_and_zero_length_entries() {
+        assert_eq!(vec![EIREntry::Flags(0x06)], parse(b"\x02\x01\x06\x09\x09Gra"));
+        assert_eq!(vec![EIREntry::Flags(0x06)], parse(b"\x02\x01\x06\x00\x02\x01\x06"));
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(data in prop::collection::vec(any::<u8>(), 0..64)) {
+            parse(&data);
+        }
+
+        #[test]
+        fn parses_every_well_formed_entry(
+            entries in prop::collection::vec((any::<u8>(), prop::collection::vec(any::<u8>(), 0..30)), 0..8),
+        ) {
+            let data = entries
+                .iter()
+                .flat_map(|(entry_type, entry_data)| {
+                    [vec![entry_data.len() as u8 + 1, *entry_type], entry_data.clone()].concat()
+                })
+                .collect::<Vec<_>>();
+
+            prop_assert_eq!(entries.len(), parse(&data).len());
+        }
+
+        #[test]
+        fn parses_beacons_with_trailing_noise(noise in prop::collection::vec(any::<u8>(), 0..32)) {
+            let data = [BEACON.to_vec(), noise].concat();
+            let entries = parse(&data);
+
+            prop_assert!(matches!(
+                entries.first(),
+                Some(EIREntry::ManufacturerSpecific(ManufacturerSpecificEntry::Apple(AppleEntry::Beacon(_))))
+            ));
+        }
     }
 }
@@ -1,3 +1,4 @@
+use bm_units::CentiCelsius;
 use chrono::{DateTime, TimeZone, Utc};
 use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
@@ -15,7 +16,7 @@ pub struct DHT22Data {
 #[derive(Serialize, Deserialize)]
 pub struct DHT22Reading {
     at: DateTime<Utc>,
-    temp: u16,
+    temp: CentiCelsius,
     humidity: u16,
 }
 
@@ -41,12 +42,12 @@ impl DHT22Data {
             .optional()
     }
 
-    pub fn insert_reading(&self, temperature: u16, humidity: u16) -> Result<()> {
+    pub fn insert_reading(&self, temperature: CentiCelsius, humidity: u16) -> Result<()> {
         let when = chrono::Utc::now().naive_utc();
 
         self.connection().execute(
             "INSERT INTO dht22_readings (id, at, temp, humidity) values (?1, ?2, ?3, ?4)",
-            params![self.id, when, temperature, humidity],
+            params![self.id, when, temperature.centi_celsius(), humidity],
         )?;
 
         Ok(())
@@ -63,7 +64,7 @@ impl DHT22Data {
             .query_map(params![self.id, from.timestamp(), to_excl.timestamp()], |row| {
                 Ok(DHT22Reading {
                     at: Utc.timestamp(row.get(0)?, 0),
-                    temp: row.get(1)?,
+                    temp: CentiCelsius::new(row.get(1)?),
                     humidity: row.get(2)?,
                 })
             })?
@@ -1,4 +1,5 @@
 use bm_tilt::TiltColor;
+use bm_units::{Fahrenheit, SpecificGravity};
 use chrono::{DateTime, TimeZone, Utc};
 use rusqlite::{params, Connection, Result};
 use serde::{Deserialize, Serialize};
@@ -9,8 +10,8 @@ use super::WrappedConnection;
 #[derive(Serialize, Deserialize)]
 pub struct TiltReading {
     pub at: DateTime<Utc>,
-    pub fahrenheit: u16,
-    pub gravity: u16,
+    pub fahrenheit: Fahrenheit,
+    pub gravity: SpecificGravity,
 }
 
 #[derive(Clone)]
@@ -27,12 +28,12 @@ impl TiltData {
         }
     }
 
-    pub fn insert_reading(&self, fahrenheit: u16, gravity: u16) -> Result<()> {
+    pub fn insert_reading(&self, fahrenheit: Fahrenheit, gravity: SpecificGravity) -> Result<()> {
         let at = Utc::now().timestamp();
 
         self.connection().execute(
             "INSERT INTO tilt_readings (at, which, temp, grav) values (?1, ?2, ?3, ?4)",
-            params![at, self.color, fahrenheit, gravity],
+            params![at, self.color, fahrenheit.degrees(), gravity.thousandths()],
         )?;
 
         Ok(())
@@ -47,8 +48,8 @@ impl TiltData {
             .query_map(params![&self.color, from.timestamp(), to_excl.timestamp()], |row| {
                 Ok(TiltReading {
                     at: Utc.timestamp(row.get(0)?, 0),
-                    fahrenheit: row.get(1)?,
-                    gravity: row.get(2)?,
+                    fahrenheit: Fahrenheit::new(row.get(1)?),
+                    gravity: SpecificGravity::from_thousandths(row.get(2)?),
                 })
             })?
             .collect();
@@ -0,0 +1,99 @@
+//! Runs a simulated Grainfather controller over stdio.
+//!
+//! Each line read from stdin is treated as a frame written to the controller (i.e. a
+//! command, or part of a recipe), and each notification emitted by the controller is
+//! written to stdout as a line.
+//!
+//! An optional argument sets how many times faster than real time the simulation runs.
+//! Logging is configured through `RUST_LOG`, and goes to stderr.
+//!
+//! This is only built with the `simulator-bin` feature, e.g.
+//! `cargo run -p bm-grainfather --features simulator-bin --bin gf-simulator`.
+
+use bm_grainfather::simulator::Simulator;
+use bm_grainfather::{Notification, COMMAND_LEN};
+use log::error;
+use std::io::{BufRead, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+fn main() {
+    pretty_env_logger::init();
+
+    let time_scale = match std::env::args().nth(1) {
+        Some(arg) => arg.parse::<f64>().expect("The time scale should be a number, e.g. 60"),
+        None => 1.0,
+    };
+
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        return;
+                    }
+                }
+
+                Err(err) => {
+                    error!("Unable to read from stdin: {:?}", err);
+                    return;
+                }
+            }
+        }
+    });
+
+    let mut simulator = Simulator::new();
+    let mut last_tick = Instant::now();
+
+    loop {
+        let timeout = TICK_INTERVAL.checked_sub(last_tick.elapsed()).unwrap_or_default();
+
+        let notifications = match receiver.recv_timeout(timeout) {
+            Ok(line) => {
+                let mut frame = line.into_bytes();
+
+                // Frames are padded with spaces, as they are when sent by a client
+                if frame.len() < COMMAND_LEN {
+                    frame.resize(COMMAND_LEN, b' ');
+                }
+
+                simulator.receive(&frame)
+            }
+
+            Err(RecvTimeoutError::Timeout) => {
+                let elapsed = last_tick.elapsed().mul_f64(time_scale);
+                last_tick = Instant::now();
+                simulator.tick(elapsed)
+            }
+
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+
+        // Whatever is reading the notifications has gone away, e.g. closed the pipe
+        if emit(&notifications).is_err() {
+            return;
+        }
+    }
+}
+
+fn emit(notifications: &[Notification]) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    for notification in notifications {
+        match notification.to_vec() {
+            Ok(frame) => {
+                stdout.write_all(&frame)?;
+                stdout.write_all(b"\n")?;
+            }
+
+            Err(err) => error!("Unable to encode notification {:?}: {:?}", notification, err),
+        }
+    }
+
+    stdout.flush()
+}
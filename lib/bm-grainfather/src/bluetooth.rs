@@ -15,13 +15,32 @@ pub const CHARACTERISTIC_ID_READ: u128 = 0x0003cdd100001000800000805f9b0131;
 pub const CHARACTERISTIC_ID_WRITE: u128 = 0x0003cdd200001000800000805f9b0131;
 
 /// Searches for the presence of the Grainfather's [service id](crate::SERVICE_ID)
-/// in the provided extended information report.
+/// in the provided extended information report, in any of the forms a service may
+/// be advertised in.
 pub fn has_grainfather_service_id(report: &EIRData) -> bool {
-    report.into_iter().any(|entry| {
-        if let EIREntry::ServiceIds(ids) = entry {
-            return ids.iter().any(|id| id.as_u128() == SERVICE_ID);
-        }
+    report.into_iter().any(|entry| entry.service_ids().any(|id| id.as_u128() == SERVICE_ID))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn finds_short_service_ids() {
+        assert!(has_grainfather_service_id(&EIRData::from(&b"\x02\x01\x06\x03\x02\xd0\xcd"[..])));
+        assert!(!has_grainfather_service_id(&EIRData::from(&b"\x02\x01\x06\x03\x02\xd1\xcd"[..])));
+    }
+
+    #[test]
+    fn finds_full_service_ids() {
+        let advertise = |ids: &[u128]| {
+            let ids = ids.iter().map(|id| Uuid::from_u128(*id)).collect::<Vec<_>>();
+
+            EIRBuilder::new().with_flags(0x06).with_name("Grain").with_service_ids(&ids).build().unwrap()
+        };
 
-        return false;
-    })
+        assert!(has_grainfather_service_id(&EIRData::from(&advertise(&[CHARACTERISTIC_ID_READ, SERVICE_ID])[..])));
+        assert!(!has_grainfather_service_id(&EIRData::from(&advertise(&[CHARACTERISTIC_ID_READ])[..])));
+    }
 }
@@ -1,17 +1,15 @@
-//! Provides a client to make it easy to work with a Grainfather controller.
+//! Provides a client to make it easy to work with a Grainfather controller over bluetooth.
 
 use crate::bluetooth::*;
-use crate::{Command, Notification, Recipe};
+use crate::transport::{FrameHandler, Transport, TransportError};
+
+pub use crate::{Client, NotificationHandler};
 
 use ::btleplug::{
-    api::{Characteristic, NotificationHandler as BtlePlugNotificationHandler, Peripheral, UUID},
-    Error, Result as BtlePlugResult,
+    api::{Characteristic, Peripheral, UUID},
+    Error,
 };
 
-use std::convert::TryFrom;
-
-pub type NotificationHandler = Box<dyn FnMut(Notification) + Send>;
-
 /// Possible errors encountered during the construction of a client.
 #[derive(Debug)]
 pub enum ClientError {
@@ -25,35 +23,47 @@ pub enum ClientError {
     ReadCharacteristic,
 }
 
-trait ClientImpl: Send + Sync + std::fmt::Debug {
-    fn is_connected(&self) -> bool;
-    fn connect(&self) -> BtlePlugResult<()>;
-    fn command(&self, characteristic: &Characteristic, data: &[u8]) -> BtlePlugResult<()>;
-    fn discover_characteristics(&self) -> BtlePlugResult<Vec<Characteristic>>;
-    fn on_notification(&self, handler: BtlePlugNotificationHandler);
-    fn subscribe(&self, characteristic: &Characteristic) -> BtlePlugResult<()>;
-}
-
+/// A [transport](crate::transport::Transport) which carries frames to and from a controller
+/// over bluetooth, using a [`btleplug::api::Peripheral`](::btleplug::api::Peripheral).
 #[derive(Debug)]
-struct BtleplugClientImpl<P>
+pub struct BtleplugTransport<P>
 where
     P: Peripheral,
 {
     p: P,
+    read: Characteristic,
+    write: Characteristic,
 }
 
-impl<P> BtleplugClientImpl<P>
+impl<P> BtleplugTransport<P>
 where
     P: Peripheral,
 {
-    pub fn new(peripheral: P) -> Self {
-        Self {
-            p: peripheral,
+    /// Constructs the transport, connecting the peripheral and discovering its characteristics.
+    pub fn new(peripheral: P) -> Result<Self, ClientError> {
+        if !peripheral.is_connected() {
+            peripheral.connect().map_err(ClientError::Connect)?
         }
+
+        let cs = peripheral.discover_characteristics().map_err(ClientError::DiscoverCharacteristics)?;
+
+        let rc_id = UUID::B128(CHARACTERISTIC_ID_READ.to_le_bytes());
+        let rc = cs.iter().find(|c| c.uuid == rc_id).ok_or(ClientError::ReadCharacteristic)?;
+
+        let wc_id = UUID::B128(CHARACTERISTIC_ID_WRITE.to_le_bytes());
+        let wc = cs.iter().find(|c| c.uuid == wc_id).ok_or(ClientError::WriteCharacteristic)?;
+
+        let result = Self {
+            read: rc.clone(),
+            write: wc.clone(),
+            p: peripheral,
+        };
+
+        Ok(result)
     }
 }
 
-impl<P> ClientImpl for BtleplugClientImpl<P>
+impl<P> Transport for BtleplugTransport<P>
 where
     P: Peripheral,
 {
@@ -61,32 +71,34 @@ where
         self.p.is_connected()
     }
 
-    fn connect(&self) -> BtlePlugResult<()> {
-        self.p.connect()
+    fn write(&self, data: &[u8]) -> Result<(), TransportError> {
+        Ok(self.p.command(&self.write, data)?)
     }
 
-    fn command(&self, characteristic: &Characteristic, data: &[u8]) -> BtlePlugResult<()> {
-        self.p.command(characteristic, data)
+    fn on_notification(&self, mut handler: FrameHandler) {
+        self.p.on_notification(Box::new(move |value_notification| handler(value_notification.value.as_slice())))
     }
 
-    fn discover_characteristics(&self) -> BtlePlugResult<Vec<Characteristic>> {
-        self.p.discover_characteristics()
+    fn subscribe(&self) -> Result<(), TransportError> {
+        Ok(self.p.subscribe(&self.read)?)
     }
 
-    fn on_notification(&self, handler: BtlePlugNotificationHandler) {
-        self.p.on_notification(handler)
-    }
+    fn connect(&self) -> Result<(), TransportError> {
+        if !self.p.is_connected() {
+            self.p.connect()?;
+        }
 
-    fn subscribe(&self, characteristic: &Characteristic) -> BtlePlugResult<()> {
-        self.p.subscribe(characteristic)
+        Ok(())
     }
 }
 
-#[derive(Debug)]
-pub struct Client {
-    gf: Box<dyn ClientImpl>,
-    read: Characteristic,
-    write: Characteristic,
+impl From<Error> for TransportError {
+    fn from(other: Error) -> Self {
+        match other {
+            Error::NotConnected => Self::NotConnected,
+            other => Self::Other(format!("{:?}", other)),
+        }
+    }
 }
 
 impl Client {
@@ -99,76 +111,6 @@ impl Client {
     where
         P: Peripheral + 'static,
     {
-        let gf = Box::new(BtleplugClientImpl::new(peripheral));
-
-        if !gf.is_connected() {
-            gf.connect().map_err(ClientError::Connect)?
-        }
-
-        let cs = gf.discover_characteristics().map_err(ClientError::DiscoverCharacteristics)?;
-
-        let rc_id = UUID::B128(CHARACTERISTIC_ID_READ.to_le_bytes());
-        let rc = cs.iter().find(|c| c.uuid == rc_id).ok_or(ClientError::ReadCharacteristic)?;
-
-        let wc_id = UUID::B128(CHARACTERISTIC_ID_WRITE.to_le_bytes());
-        let wc = cs.iter().find(|c| c.uuid == wc_id).ok_or(ClientError::WriteCharacteristic)?;
-
-        let result = Self {
-            gf,
-            read: rc.clone(),
-            write: wc.clone(),
-        };
-
-        Ok(result)
-    }
-
-    /// Determines whether the client is connected.
-    pub fn is_connected(&self) -> bool {
-        self.gf.is_connected()
-    }
-
-    /// Despatches a command to the grainfather controller.
-    pub fn command(&self, command: &Command) -> Result<(), Error> {
-        println!("[S]: {:?}", command);
-        self.gf.command(&self.write, command.to_vec().as_ref())
-    }
-
-    /// Sends a recipe to the to the grainfather controller.
-    pub fn send_recipe(&self, recipe: &Recipe) -> Result<(), Error> {
-        println!("[S]: Recipe with name {}", recipe.name);
-
-        // self.gf.command(&self.write, &Command::SetLocalBoilTemperature(recipe.boil_temperature).to_vec())?;
-
-        // // Otherwise the controller becomes unresponsive, there's got to be
-        // // a better approach than this though.
-        // std::thread::sleep(std::time::Duration::from_millis(200));
-
-        for command in recipe.to_commands().iter() {
-            self.gf.command(&self.write, command.as_ref())?
-        }
-
-        Ok(())
-    }
-
-    /// Subscribes to notifications issued by the grainfather controller.
-    pub fn subscribe(&self, mut handler: NotificationHandler) -> Result<(), Error> {
-        const NOTIFICATION_LEN: usize = 17;
-        const NOTIFICATION_BUF_COUNT: usize = NOTIFICATION_LEN * 8;
-        let mut gf_notification_buf = Vec::<u8>::with_capacity(NOTIFICATION_BUF_COUNT);
-
-        self.gf.on_notification(Box::new(move |mut value_notification| {
-            gf_notification_buf.append(&mut value_notification.value);
-
-            let notification_count = gf_notification_buf.len() / NOTIFICATION_LEN;
-            let notifications_len = notification_count * NOTIFICATION_LEN;
-
-            for notification in gf_notification_buf.drain(..notifications_len).as_slice().chunks_exact(NOTIFICATION_LEN)
-            {
-                let notification = Notification::try_from(notification).unwrap();
-                handler(notification);
-            }
-        }));
-
-        self.gf.subscribe(&self.read)
+        Ok(Self::new(Box::new(BtleplugTransport::new(peripheral)?)))
     }
 }
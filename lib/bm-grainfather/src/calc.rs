@@ -1,6 +1,9 @@
 // Useful information at
 //   https://byo.com/article/calculating-water-usage-advanced-brewing/
 
+use crate::Voltage;
+use bm_units::{CentiCelsius, Millilitres};
+
 // NOTE: sparge heater takes 20m to raise 18 litres to 75 deg
 // docs recommend to start heating once doughed in
 
@@ -25,3 +28,76 @@ pub fn sparge_water_metric(final_volume_l: f64, grain_bill_kg: f64) -> f64 {
 
     (pre_boil_volume - mash_water_volume) + water_loss_in_grain
 }
+
+/// The temperature to heat the mash water to so that, once the grain is added,
+/// the mash settles at the target temperature.
+///
+/// Uses the thermodynamic relationship from "How to Brew", where 0.41 is the
+/// specific heat of grain relative to water, taking a litre of water as a kilogram.
+///
+/// Returns `None` unless there's a positive amount of both grain and water.
+pub fn strike_temperature_metric(
+    grain_bill_kg: f64,
+    grain_temperature: CentiCelsius,
+    mash_water: Millilitres,
+    target: CentiCelsius,
+) -> Option<CentiCelsius> {
+    if !grain_bill_kg.is_finite() || grain_bill_kg <= 0.0 || mash_water <= Millilitres::new(0) {
+        return None;
+    }
+
+    let ratio = mash_water.litres() / grain_bill_kg;
+    let target = target.celsius();
+
+    Some(CentiCelsius::from_celsius((0.41 / ratio) * (target - grain_temperature.celsius()) + target))
+}
+
+/// The power drawn by the heating element of a controller with the given mains voltage,
+/// when running at the given percentage of its full power. The 230 V element is rated at
+/// 2000 W, and the 110 V element at 1600 W.
+pub fn heat_power_watts(voltage: &Voltage, heat_power_output_percentage: u8) -> u32 {
+    let element_watts = match voltage {
+        Voltage::V110 => 1600,
+        Voltage::V230 => 2000,
+    };
+
+    element_watts * u32::from(heat_power_output_percentage.min(100)) / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calculates_strike_temperature() {
+        let strike = strike_temperature_metric(
+            4.0,
+            CentiCelsius::from_degrees(20),
+            Millilitres::from_litres(13.25),
+            CentiCelsius::from_degrees(65),
+        );
+
+        assert_eq!(Some(CentiCelsius::new(7057)), strike);
+    }
+
+    #[test]
+    fn rejects_unusable_grain_bills() {
+        for &grain_bill_kg in &[0.0, -4.0, f64::NAN, f64::INFINITY] {
+            let strike = strike_temperature_metric(
+                grain_bill_kg,
+                CentiCelsius::from_degrees(20),
+                Millilitres::from_litres(13.25),
+                CentiCelsius::from_degrees(65),
+            );
+
+            assert_eq!(None, strike, "Grain bill {}", grain_bill_kg);
+        }
+    }
+
+    #[test]
+    fn calculates_heat_power() {
+        assert_eq!(1000, heat_power_watts(&Voltage::V230, 50));
+        assert_eq!(1600, heat_power_watts(&Voltage::V110, 100));
+        assert_eq!(0, heat_power_watts(&Voltage::V110, 0));
+    }
+}
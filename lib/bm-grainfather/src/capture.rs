@@ -0,0 +1,391 @@
+//! Recording and reading captures of the raw frames exchanged with a controller.
+//!
+//! A capture is stored as JSON Lines, with one [CapturedFrame](crate::capture::CapturedFrame)
+//! per line, so that captures taken on brew day can be inspected by hand, replayed through a
+//! client, and kept as regression fixtures.
+
+use crate::transport::{FrameHandler, Transport, TransportError};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The direction a captured frame travelled in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
+    /// A frame written to the controller, i.e. a command or part of a recipe.
+    Sent,
+
+    /// A chunk of notification data received from the controller, this may contain
+    /// several notifications, or only part of one.
+    Received,
+}
+
+/// A single frame in a capture.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CapturedFrame {
+    /// The time since the capture started, in microseconds.
+    pub elapsed_us: u64,
+    pub direction: Direction,
+    #[serde(with = "frame_data")]
+    pub data: Vec<u8>,
+}
+
+impl CapturedFrame {
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_micros(self.elapsed_us)
+    }
+}
+
+/// Possible errors encountered reading a capture.
+#[derive(Debug)]
+pub enum CaptureError {
+    Io(std::io::Error),
+
+    /// The line with the given (one-based) number isn't a valid frame.
+    InvalidFrame {
+        line: usize,
+        error: serde_json::Error,
+    },
+}
+
+impl From<std::io::Error> for CaptureError {
+    fn from(other: std::io::Error) -> Self {
+        Self::Io(other)
+    }
+}
+
+/// Writes frames to a capture as they're sent and received.
+#[derive(Debug)]
+pub struct CaptureWriter<W> {
+    writer: W,
+    started: Instant,
+}
+
+impl<W> CaptureWriter<W>
+where
+    W: Write,
+{
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            started: Instant::now(),
+        }
+    }
+
+    /// Records a frame, timestamped relative to the creation of the writer. Each frame
+    /// is flushed as it's written so that the capture survives a crash.
+    pub fn record(&mut self, direction: Direction, data: &[u8]) -> std::io::Result<()> {
+        let frame = CapturedFrame {
+            elapsed_us: self.started.elapsed().as_micros() as u64,
+            direction,
+            data: data.to_vec(),
+        };
+
+        serde_json::to_writer(&mut self.writer, &frame)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// Reads every frame from a capture, blank lines are ignored.
+pub fn read_capture<R>(reader: R) -> Result<Vec<CapturedFrame>, CaptureError>
+where
+    R: BufRead,
+{
+    let mut frames = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let frame = serde_json::from_str(&line).map_err(|error| CaptureError::InvalidFrame {
+            line: index + 1,
+            error,
+        })?;
+
+        frames.push(frame);
+    }
+
+    Ok(frames)
+}
+
+/// A transport that records every frame passing through another transport.
+pub struct CapturingTransport {
+    inner: Box<dyn Transport>,
+    capture: Arc<Mutex<CaptureWriter<Box<dyn Write + Send>>>>,
+}
+
+impl CapturingTransport {
+    /// Wraps a transport, recording every frame written to it and every chunk of
+    /// notification data it delivers.
+    pub fn new<W>(inner: Box<dyn Transport>, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+
+        Self {
+            inner,
+            capture: Arc::new(Mutex::new(CaptureWriter::new(writer))),
+        }
+    }
+
+    fn record(capture: &Mutex<CaptureWriter<Box<dyn Write + Send>>>, direction: Direction, data: &[u8]) {
+        if let Err(err) = capture.lock().unwrap().record(direction, data) {
+            warn!("Unable to capture {:?} frame {:?}: {:?}", direction, String::from_utf8_lossy(data), err);
+        }
+    }
+}
+
+impl Transport for CapturingTransport {
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), TransportError> {
+        Self::record(&self.capture, Direction::Sent, data);
+        self.inner.write(data)
+    }
+
+    fn on_notification(&self, mut handler: FrameHandler) {
+        let capture = self.capture.clone();
+
+        self.inner.on_notification(Box::new(move |data| {
+            Self::record(&capture, Direction::Received, data);
+            handler(data)
+        }))
+    }
+
+    fn subscribe(&self) -> Result<(), TransportError> {
+        self.inner.subscribe()
+    }
+
+    fn connect(&self) -> Result<(), TransportError> {
+        self.inner.connect()
+    }
+}
+
+impl std::fmt::Debug for CapturingTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CapturingTransport").field("inner", &self.inner).finish()
+    }
+}
+
+/// A transport that plays back the frames received in a capture, frames written
+/// to it are discarded.
+pub struct ReplayTransport {
+    frames: Arc<Vec<CapturedFrame>>,
+    handler: Arc<Mutex<Option<FrameHandler>>>,
+    paced: bool,
+    started: AtomicBool,
+}
+
+impl ReplayTransport {
+    /// Constructs a transport that plays back the given frames, paced playback preserves
+    /// the timing of the capture, otherwise the whole capture is delivered when subscribing.
+    pub fn new(frames: Vec<CapturedFrame>, paced: bool) -> Self {
+        Self {
+            frames: Arc::new(frames),
+            handler: Arc::new(Mutex::new(None)),
+            paced,
+            started: AtomicBool::new(false),
+        }
+    }
+
+    fn run(frames: &[CapturedFrame], handler: &Mutex<Option<FrameHandler>>, paced: bool) {
+        let started = Instant::now();
+
+        for frame in frames.iter().filter(|frame| frame.direction == Direction::Received) {
+            if paced {
+                if let Some(delay) = frame.elapsed().checked_sub(started.elapsed()) {
+                    std::thread::sleep(delay);
+                }
+            }
+
+            if let Some(handler) = handler.lock().unwrap().as_mut() {
+                handler(frame.data.as_slice());
+            }
+        }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn write(&self, _data: &[u8]) -> Result<(), TransportError> {
+        Ok(())
+    }
+
+    fn on_notification(&self, handler: FrameHandler) {
+        *self.handler.lock().unwrap() = Some(handler);
+    }
+
+    /// Plays back the capture, an unpaced capture is played back before this returns.
+    fn subscribe(&self) -> Result<(), TransportError> {
+        if self.started.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        if self.paced {
+            let frames = self.frames.clone();
+            let handler = self.handler.clone();
+
+            std::thread::spawn(move || Self::run(&frames, &handler, true));
+        } else {
+            Self::run(&self.frames, &self.handler, false);
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ReplayTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplayTransport").field("frames", &self.frames.len()).field("paced", &self.paced).finish()
+    }
+}
+
+/// Frames are almost always ASCII, so they're stored as strings to keep captures readable,
+/// anything that isn't valid UTF-8 is stored as an array of bytes instead.
+mod frame_data {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum FrameData {
+        Text(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub fn serialize<S>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match std::str::from_utf8(value) {
+            Ok(text) => serializer.serialize_str(text),
+            Err(_) => value.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match FrameData::deserialize(deserializer)? {
+            FrameData::Text(text) => text.into_bytes(),
+            FrameData::Bytes(bytes) => bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::*;
+    use crate::{Client, InteractionCode, Notification, NOTIFICATION_LEN};
+    use bm_units::CentiCelsius;
+    use std::convert::TryFrom;
+    use std::sync::mpsc;
+
+    const STATUS_CAPTURE: &[u8] = include_bytes!("../test-data/status.jsonl");
+
+    #[test]
+    fn round_trips_captures() {
+        let mut writer = CaptureWriter::new(Vec::new());
+        writer.record(Direction::Sent, b"K1,                ").unwrap();
+        writer.record(Direction::Received, &[b'X', 0xff, b',']).unwrap();
+
+        let frames = read_capture(writer.writer.as_slice()).unwrap();
+
+        assert_eq!(2, frames.len());
+        assert_eq!(Direction::Sent, frames[0].direction);
+        assert_eq!(b"K1,                ".to_vec(), frames[0].data);
+        assert_eq!(vec![b'X', 0xff, b','], frames[1].data);
+        assert!(frames[0].elapsed() <= frames[1].elapsed());
+    }
+
+    #[test]
+    fn reports_invalid_lines() {
+        let capture = "{\"elapsed_us\":0,\"direction\":\"Sent\",\"data\":\"L\"}\n\nnot json\n";
+
+        assert!(matches!(
+            read_capture(capture.as_bytes()),
+            Err(CaptureError::InvalidFrame {
+                line: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn parses_captured_notifications() {
+        let received = read_capture(STATUS_CAPTURE)
+            .unwrap()
+            .into_iter()
+            .filter(|frame| frame.direction == Direction::Received)
+            .flat_map(|frame| frame.data)
+            .collect::<Vec<_>>();
+
+        let notifications = received
+            .chunks_exact(NOTIFICATION_LEN)
+            .map(|chunk| Notification::try_from(chunk).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(5, notifications.len());
+        assert!(matches!(notifications[0], Notification::FirmwareVersion(_)));
+    }
+
+    #[test]
+    fn replays_captures_through_a_client() {
+        let client = Client::replay(read_capture(STATUS_CAPTURE).unwrap(), false);
+        let (sender, receiver) = mpsc::channel();
+
+        client.subscribe(Box::new(move |notification| sender.send(notification).unwrap())).unwrap();
+
+        // An unpaced capture has been played back in full by the time subscribing returns
+        assert_eq!(
+            vec![
+                Notification::FirmwareVersion(FirmwareVersion {
+                    firmware_version: "1.4.3".to_string(),
+                }),
+                Notification::Temp(Temp {
+                    desired: CentiCelsius::new(6500),
+                    current: CentiCelsius::new(6480),
+                }),
+                Notification::Timer(Timer {
+                    active: true,
+                    remaining_minutes: 60,
+                    remaining_seconds: 0,
+                    total_start_time: 60,
+                }),
+                Notification::Status1(Status1 {
+                    heat_active: true,
+                    pump_active: true,
+                    auto_mode_active: true,
+                    step_ramp_active: false,
+                    interaction_mode_active: false,
+                    interaction_code: InteractionCode::None,
+                    step_number: 1,
+                    delayed_heat_mode_active: false,
+                }),
+                Notification::Status2(Status2 {
+                    heat_power_output_percentage: 80,
+                    timer_paused: false,
+                    step_mash_mode: false,
+                    recipe_interrupted: false,
+                    manual_power_mode: false,
+                    sparge_water_alert_displayed: false,
+                }),
+            ],
+            receiver.try_iter().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,724 @@
+//! Provides a client to make it easy to work with a Grainfather controller.
+
+use crate::capture::{CapturedFrame, CapturingTransport, ReplayTransport};
+use crate::notifications::{Boil, FirmwareVersion, VoltageAndUnits};
+use crate::queue::{CommandQueue, Priority, QueueStats};
+use crate::simulator::{SimulatedTransport, Simulator};
+use crate::trace::{TraceHandler, Tracer, TracingTransport};
+use crate::transport::{Transport, TransportError};
+use crate::{
+    Command, DisconnectOption, Error, FramingStats, Notification, NotificationFramer, Recipe, RecipeDelay, StepNumber,
+};
+
+use bm_units::CentiCelsius;
+use log::{debug, warn};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+pub type NotificationHandler = Box<dyn FnMut(Notification) + Send>;
+
+/// Controls the pacing and verification of a recipe [upload](crate::Client::upload_recipe).
+#[derive(Clone, Debug)]
+pub struct UploadOptions {
+    /// The time to wait between frames, the controller becomes unresponsive if the
+    /// frames of a recipe arrive back-to-back.
+    pub frame_interval: Duration,
+
+    /// The time to wait for the controller to report that it has loaded the recipe.
+    pub verify_timeout: Duration,
+
+    /// The number of times to send the recipe before giving up.
+    pub attempts: u32,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            frame_interval: Duration::from_millis(200),
+            verify_timeout: Duration::from_secs(5),
+            attempts: 3,
+        }
+    }
+}
+
+/// The progress of a recipe upload, attempts are numbered from one.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum UploadProgress {
+    /// The given number of frames have been sent.
+    Sending {
+        attempt: u32,
+        sent: usize,
+        total: usize,
+    },
+
+    /// Every frame has been sent, and the controller's status is being watched to
+    /// see whether it loaded the recipe.
+    Verifying {
+        attempt: u32,
+    },
+}
+
+/// The kinds of notification sent in reply to a request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ReplyKind {
+    FirmwareVersion,
+    VoltageAndUnits,
+    Boil,
+
+    /// A status reporting that the controller isn't running a session.
+    NoSession,
+
+    /// A status reporting that a recipe has been loaded, and the controller is at the given step.
+    RecipeLoaded(StepNumber),
+}
+
+impl ReplyKind {
+    fn matches(self, notification: &Notification) -> bool {
+        match (self, notification) {
+            (Self::FirmwareVersion, Notification::FirmwareVersion(_))
+            | (Self::VoltageAndUnits, Notification::VoltageAndUnits(_))
+            | (Self::Boil, Notification::Boil(_)) => true,
+
+            (Self::NoSession, Notification::Status1(status)) => !status.auto_mode_active,
+
+            (Self::RecipeLoaded(step_number), Notification::Status1(status)) => {
+                status.auto_mode_active && status.step_number == step_number
+            }
+
+            _ => false,
+        }
+    }
+}
+
+struct PendingReply {
+    kind: ReplyKind,
+    sender: oneshot::Sender<Notification>,
+}
+
+/// Routes notifications to the subscribed handler, and to requests awaiting a reply.
+#[derive(Default)]
+struct Dispatcher {
+    handler: Option<NotificationHandler>,
+    pending: Vec<PendingReply>,
+}
+
+impl Dispatcher {
+    fn dispatch(&mut self, notification: Notification) {
+        self.discard_abandoned();
+
+        // Replies arrive in the order requests are made, a request may still time out
+        // before its reply is sent, in which case the reply goes to the next request
+        while let Some(index) = self.pending.iter().position(|pending| pending.kind.matches(&notification)) {
+            let pending = self.pending.remove(index);
+
+            if pending.sender.send(notification.clone()).is_ok() {
+                break;
+            }
+        }
+
+        if let Some(handler) = self.handler.as_mut() {
+            handler(notification);
+        }
+    }
+
+    /// Discards the requests that have stopped waiting for a reply, e.g. they've timed
+    /// out, so that they don't build up when their replies never arrive.
+    fn discard_abandoned(&mut self) {
+        self.pending.retain(|pending| !pending.sender.is_closed());
+    }
+}
+
+impl std::fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dispatcher").field("pending", &self.pending.len()).finish()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Subscription {
+    None,
+    Registered,
+    Subscribed,
+}
+
+#[derive(Debug)]
+pub struct Client {
+    gf: Arc<dyn Transport>,
+    queue: CommandQueue,
+    tracer: Arc<Tracer>,
+    dispatcher: Arc<Mutex<Dispatcher>>,
+    framer: Arc<Mutex<NotificationFramer>>,
+    subscription: Mutex<Subscription>,
+    request_timeout: Duration,
+}
+
+impl Client {
+    /// The default time to wait for the controller to reply to a request.
+    pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// The default minimum gap between frames written to the controller.
+    pub const DEFAULT_MIN_FRAME_INTERVAL: Duration = Duration::from_millis(50);
+
+    pub(crate) fn new(gf: Box<dyn Transport>) -> Self {
+        let gf: Arc<dyn Transport> = Arc::from(gf);
+        let tracer = Arc::new(Tracer::default());
+
+        Self {
+            queue: Self::queue(&gf, &tracer, Self::DEFAULT_MIN_FRAME_INTERVAL),
+            gf,
+            tracer,
+            dispatcher: Arc::new(Mutex::new(Dispatcher::default())),
+            framer: Arc::new(Mutex::new(NotificationFramer::new())),
+            subscription: Mutex::new(Subscription::None),
+            request_timeout: Self::DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Constructs a client that communicates with a controller over the given transport.
+    pub fn from_transport<T>(transport: T) -> Self
+    where
+        T: Transport + 'static,
+    {
+        Self::new(Box::new(transport))
+    }
+
+    /// Constructs a client connected to a [simulated controller](crate::simulator::Simulator)
+    /// rather than a real one. Simulated time passes `time_scale` times faster than real time.
+    pub fn simulated(simulator: Simulator, time_scale: f64) -> Self {
+        Self::new(Box::new(SimulatedTransport::new(simulator, time_scale)))
+    }
+
+    /// Constructs a client that plays back the notification data received in a capture,
+    /// this goes through the same parsing as notifications from a real controller. Paced
+    /// playback preserves the timing of the capture, otherwise the whole capture is
+    /// delivered when subscribing.
+    pub fn replay(frames: Vec<CapturedFrame>, paced: bool) -> Self {
+        Self::new(Box::new(ReplayTransport::new(frames, paced)))
+    }
+
+    /// Records every frame sent and received by the client to the given writer as JSON
+    /// Lines, see [capture](crate::capture). This should be used before subscribing so
+    /// that every notification is recorded.
+    pub fn with_capture<W>(self, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let gf: Arc<dyn Transport> = Arc::new(CapturingTransport::new(Box::new(self.gf.clone()), writer));
+
+        // Replacing the queue stops the writer for the uncaptured transport
+        Self {
+            queue: Self::queue(&gf, &self.tracer, self.queue.min_interval()),
+            gf,
+            ..self
+        }
+    }
+
+    /// Constructs a queue which writes to the given transport, tracing each frame written.
+    fn queue(gf: &Arc<dyn Transport>, tracer: &Arc<Tracer>, min_interval: Duration) -> CommandQueue {
+        CommandQueue::new(Arc::new(TracingTransport::new(gf.clone(), tracer.clone())), min_interval)
+    }
+
+    /// Reports every frame sent to and received from the controller to the given handler,
+    /// decoded where possible, see [trace](crate::trace). This replaces any previous
+    /// handler, and `None` stops tracing.
+    pub fn set_tracer(&self, handler: Option<TraceHandler>) {
+        self.tracer.set_handler(handler);
+    }
+
+    /// Sets the time to wait for the controller to reply to requests such as
+    /// [firmware_version](crate::Client::firmware_version).
+    pub fn with_request_timeout(self, request_timeout: Duration) -> Self {
+        Self {
+            request_timeout,
+            ..self
+        }
+    }
+
+    /// Sets the minimum gap between frames written to the controller, however they're
+    /// issued, so that it isn't overwhelmed.
+    pub fn with_min_frame_interval(self, min_frame_interval: Duration) -> Self {
+        self.queue.set_min_interval(min_frame_interval);
+        self
+    }
+
+    /// Describes the commands waiting to be written to the controller, and how long
+    /// they've been waiting.
+    pub fn queue_stats(&self) -> QueueStats {
+        self.queue.stats()
+    }
+
+    /// Describes how well the notification data received so far has been framed, a
+    /// growing number of discarded bytes indicates a poor connection.
+    pub fn framing_stats(&self) -> FramingStats {
+        self.framer.lock().unwrap().stats()
+    }
+
+    /// Determines whether the client is connected.
+    pub fn is_connected(&self) -> bool {
+        self.gf.is_connected()
+    }
+
+    /// Re-establishes a lost connection to the controller, and resumes the delivery of
+    /// notifications if the client was subscribed, or had failed to subscribe. This blocks
+    /// while connecting.
+    pub fn reconnect(&self) -> Result<(), Error> {
+        self.gf.connect()?;
+
+        let mut subscription = self.subscription.lock().unwrap();
+
+        if *subscription != Subscription::None {
+            self.gf.subscribe()?;
+            *subscription = Subscription::Subscribed;
+        }
+
+        Ok(())
+    }
+
+    /// Despatches a command to the grainfather controller, blocking until it has been
+    /// written. Commands are queued behind any others waiting to be written, apart from
+    /// those with an [urgent](crate::Priority::Urgent) priority, which go ahead of them.
+    ///
+    /// This may block for as long as it takes to write every queued command, including
+    /// whole recipes, so async callers should use [command_async](crate::Client::command_async).
+    pub fn command(&self, command: &Command) -> Result<(), Error> {
+        debug!("Sending {:?}", command);
+
+        self.queue.write(Priority::of(command), vec![command.to_vec()?])
+    }
+
+    /// Despatches a command without blocking, see [command](crate::Client::command).
+    pub async fn command_async(&self, command: &Command) -> Result<(), Error> {
+        debug!("Sending {:?}", command);
+
+        let written =
+            self.queue.write_async(Priority::of(command), vec![command.to_vec()?], Duration::from_secs(0), None)?;
+
+        // The result is only dropped without being sent once the queue has closed
+        Ok(written.await.unwrap_or(Err(TransportError::NotConnected))?)
+    }
+
+    /// Sends a recipe to the to the grainfather controller, writing every frame back-to-back.
+    /// Prefer [upload_recipe](crate::Client::upload_recipe), which paces the frames and checks
+    /// that the controller loaded the recipe.
+    pub fn send_recipe(&self, recipe: &Recipe) -> Result<(), Error> {
+        debug!("Sending recipe {}", recipe.name);
+
+        let commands = Self::encode_recipe(recipe)?;
+
+        self.queue.write(Priority::Normal, commands)
+    }
+
+    /// Encodes a recipe into frames, rejecting it if it isn't [valid](crate::Recipe::validate).
+    fn encode_recipe(recipe: &Recipe) -> Result<Vec<Vec<u8>>, Error> {
+        let problems = recipe.validate();
+
+        if !problems.is_empty() {
+            return Err(Error::Invalid(problems));
+        }
+
+        Ok(recipe.to_commands()?)
+    }
+
+    /// Uploads a recipe to the grainfather controller, pausing between frames so as not to
+    /// overwhelm it, then watching its status until it reports that it's running the recipe.
+    ///
+    /// Any session the controller is running is cancelled before each attempt, and the
+    /// recipe is only sent once the controller reports that it has no session, so that a
+    /// session which was already running can't be mistaken for the recipe being loaded.
+    ///
+    /// If the controller doesn't load the recipe in time, the recipe is sent again, up to
+    /// the configured number of attempts. The session is also cancelled after the final
+    /// attempt, so the controller is never left running a recipe that was reported as
+    /// failing to load. Part of the recipe failing to reach the controller abandons the
+    /// upload rather than retrying it.
+    pub async fn upload_recipe<F>(&self, recipe: &Recipe, options: &UploadOptions, mut progress: F) -> Result<(), Error>
+    where
+        F: FnMut(UploadProgress),
+    {
+        let frames = Self::encode_recipe(recipe)?;
+
+        // Recipes with a delayed start wait at step zero until the delay elapses
+        let kind = ReplyKind::RecipeLoaded(match recipe.delay {
+            RecipeDelay::MinutesSeconds(_, _) => 0,
+            RecipeDelay::None => 1,
+        });
+
+        self.ensure_subscribed()?;
+
+        let total = frames.len();
+
+        for attempt in 1..=options.attempts {
+            if !self.cancel_session(options.verify_timeout).await? {
+                warn!("Controller didn't cancel its session before attempt {} of {}", attempt, options.attempts);
+                continue;
+            }
+
+            let (sent_sender, mut sent_receiver) = mpsc::unbounded_channel();
+            let (reply_sender, reply_receiver) = oneshot::channel();
+            let dispatcher = self.dispatcher.clone();
+
+            let mut pending = Some(PendingReply {
+                kind,
+                sender: reply_sender,
+            });
+
+            // The frames are queued together, and the queue reports each one as it's written
+            let written = self.queue.write_async(
+                Priority::Normal,
+                frames.clone(),
+                options.frame_interval,
+                Some(Box::new(move |index| {
+                    // Only a status reported once the final frame has been written can
+                    // confirm that this recipe was loaded
+                    if index + 1 == total {
+                        if let Some(pending) = pending.take() {
+                            dispatcher.lock().unwrap().pending.push(pending);
+                        }
+                    }
+
+                    let _ = sent_sender.send(index);
+                })),
+            )?;
+
+            while let Some(sent) = sent_receiver.recv().await {
+                progress(UploadProgress::Sending {
+                    attempt,
+                    sent: sent + 1,
+                    total,
+                });
+            }
+
+            written.await.unwrap_or(Err(TransportError::NotConnected))?;
+
+            progress(UploadProgress::Verifying {
+                attempt,
+            });
+
+            if let Ok(Ok(_)) = tokio::time::timeout(options.verify_timeout, reply_receiver).await {
+                return Ok(());
+            }
+
+            warn!("Controller didn't load recipe {} on attempt {} of {}", recipe.name, attempt, options.attempts);
+        }
+
+        self.command_async(&Command::Disconnect(DisconnectOption::CancelSession)).await?;
+
+        Err(Error::NotLoaded {
+            attempts: options.attempts,
+        })
+    }
+
+    /// Cancels any session the controller is running, returning whether it reported that
+    /// it had no session within the given time.
+    async fn cancel_session(&self, timeout: Duration) -> Result<bool, Error> {
+        let receiver = self.expect_reply(ReplyKind::NoSession);
+
+        self.command_async(&Command::Disconnect(DisconnectOption::CancelSession)).await?;
+
+        Ok(matches!(tokio::time::timeout(timeout, receiver).await, Ok(Ok(_))))
+    }
+
+    /// Requests the controller's firmware version.
+    pub async fn firmware_version(&self) -> Result<String, Error> {
+        match self.request(Command::GetFirmwareVersion, ReplyKind::FirmwareVersion).await? {
+            Notification::FirmwareVersion(FirmwareVersion {
+                firmware_version,
+            }) => Ok(firmware_version),
+            other => unreachable!("Reply {:?} doesn't match the request", other),
+        }
+    }
+
+    /// Requests the controller's power supply voltage and temperature units.
+    pub async fn voltage_and_units(&self) -> Result<VoltageAndUnits, Error> {
+        match self.request(Command::GetVoltageAndUnits, ReplyKind::VoltageAndUnits).await? {
+            Notification::VoltageAndUnits(voltage_and_units) => Ok(voltage_and_units),
+            other => unreachable!("Reply {:?} doesn't match the request", other),
+        }
+    }
+
+    /// Requests the boil temperature configured on the controller.
+    pub async fn boil_temperature(&self) -> Result<CentiCelsius, Error> {
+        match self.request(Command::GetBoilTemperature, ReplyKind::Boil).await? {
+            Notification::Boil(Boil {
+                boil_temperature,
+            }) => Ok(boil_temperature),
+            other => unreachable!("Reply {:?} doesn't match the request", other),
+        }
+    }
+
+    /// Issues a command, and waits for the first reply of the given kind received after it.
+    async fn request(&self, command: Command, kind: ReplyKind) -> Result<Notification, Error> {
+        self.ensure_subscribed()?;
+
+        let receiver = self.expect_reply(kind);
+
+        self.command_async(&command).await?;
+
+        match tokio::time::timeout(self.request_timeout, receiver).await {
+            Ok(Ok(notification)) => Ok(notification),
+
+            // The sender is only dropped without sending once the client is dropped,
+            // which can't happen while it's borrowed here
+            Ok(Err(_)) | Err(_) => Err(Error::Timeout(self.request_timeout)),
+        }
+    }
+
+    /// Registers interest in the first reply of the given kind to be received from now on.
+    fn expect_reply(&self, kind: ReplyKind) -> oneshot::Receiver<Notification> {
+        let (sender, receiver) = oneshot::channel();
+        let mut dispatcher = self.dispatcher.lock().unwrap();
+
+        dispatcher.discard_abandoned();
+        dispatcher.pending.push(PendingReply {
+            kind,
+            sender,
+        });
+
+        receiver
+    }
+
+    /// Subscribes to notifications issued by the grainfather controller, this replaces
+    /// any previously subscribed handler.
+    ///
+    /// The handler must not make requests of the client, as replies are delivered
+    /// from the same context as the handler.
+    pub fn subscribe(&self, handler: NotificationHandler) -> Result<(), Error> {
+        self.dispatcher.lock().unwrap().handler = Some(handler);
+        Ok(self.ensure_subscribed()?)
+    }
+
+    fn ensure_subscribed(&self) -> Result<(), TransportError> {
+        let mut subscription = self.subscription.lock().unwrap();
+
+        if *subscription == Subscription::None {
+            self.register_notification_handler();
+            *subscription = Subscription::Registered;
+        }
+
+        if *subscription == Subscription::Registered {
+            self.gf.subscribe()?;
+            *subscription = Subscription::Subscribed;
+        }
+
+        Ok(())
+    }
+
+    fn register_notification_handler(&self) {
+        let framer = self.framer.clone();
+        let dispatcher = self.dispatcher.clone();
+        let tracer = self.tracer.clone();
+
+        self.gf.on_notification(Box::new(move |value| {
+            let (notifications, discarded) = {
+                let mut framer = framer.lock().unwrap();
+                let discarded_before = framer.stats().discarded_bytes;
+                let notifications = framer.push_frames(value);
+                (notifications, framer.stats().discarded_bytes - discarded_before)
+            };
+
+            if discarded > 0 {
+                warn!("Discarded {} bytes of malformed notification data", discarded);
+            }
+
+            for (notification, frame) in notifications {
+                tracer.received(&notification, &frame);
+                dispatcher.lock().unwrap().dispatch(notification);
+            }
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::Status1;
+    use crate::simulator::Simulator;
+    use crate::transport::{LoopbackHandle, LoopbackTransport};
+    use crate::{InteractionCode, MashStep};
+    use std::sync::mpsc;
+
+    fn firmware_version_frame(firmware_version: &str) -> Vec<u8> {
+        Notification::FirmwareVersion(FirmwareVersion {
+            firmware_version: firmware_version.to_string(),
+        })
+        .to_vec()
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_notifications_split_across_chunks() {
+        let (transport, controller) = LoopbackTransport::new();
+        let client = Client::from_transport(transport);
+        let (sender, receiver) = mpsc::channel();
+
+        client.subscribe(Box::new(move |notification| sender.send(notification).unwrap())).unwrap();
+
+        let data = [firmware_version_frame("1.2.3"), firmware_version_frame("4.5.6")].concat();
+
+        for chunk in data.chunks(5) {
+            assert!(controller.notify(chunk));
+        }
+
+        let firmware_versions = receiver
+            .try_iter()
+            .map(|notification| match notification {
+                Notification::FirmwareVersion(FirmwareVersion {
+                    firmware_version,
+                }) => firmware_version,
+                other => panic!("Unexpected notification {:?}", other),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec!["1.2.3", "4.5.6"], firmware_versions);
+    }
+
+    #[tokio::test]
+    async fn requests_replies() {
+        let (transport, controller) = LoopbackTransport::new();
+
+        controller.respond_with(|frame| match frame.first() {
+            Some(b'X') => vec![firmware_version_frame("1.2.3")],
+            _ => Vec::new(),
+        });
+
+        let client = Client::from_transport(transport).with_request_timeout(Duration::from_millis(50));
+
+        assert_eq!("1.2.3", client.firmware_version().await.unwrap());
+        assert!(matches!(client.boil_temperature().await, Err(Error::Timeout(_))));
+
+        // The timed out request is discarded, though its reply never arrives
+        assert!(controller.notify(&firmware_version_frame("1.2.3")));
+        assert!(client.dispatcher.lock().unwrap().pending.is_empty());
+
+        controller.set_connected(false);
+        assert!(matches!(client.firmware_version().await, Err(Error::NotConnected)));
+    }
+
+    /// Reports the statuses built by the given function every few milliseconds, as a
+    /// controller does, until the test finishes.
+    fn report_status<F>(controller: &LoopbackHandle, mut status: F)
+    where
+        F: FnMut() -> Vec<Notification> + Send + 'static,
+    {
+        let controller = controller.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::delay_for(Duration::from_millis(5)).await;
+
+                for notification in status() {
+                    controller.notify(&notification.to_vec().unwrap());
+                }
+            }
+        });
+    }
+
+    fn recipe() -> Recipe {
+        Recipe {
+            mash_steps: vec![MashStep {
+                temperature: CentiCelsius::from_degrees(65),
+                minutes: 60,
+            }],
+            ..Recipe::default()
+        }
+    }
+
+    fn upload_options() -> UploadOptions {
+        UploadOptions {
+            frame_interval: Duration::from_millis(1),
+            verify_timeout: Duration::from_millis(50),
+            attempts: 2,
+        }
+    }
+
+    #[tokio::test]
+    async fn uploads_recipes() {
+        let (transport, controller) = LoopbackTransport::new();
+        let simulator = Arc::new(Mutex::new(Simulator::new()));
+        let receiver = simulator.clone();
+
+        controller.respond_with(move |frame| {
+            receiver.lock().unwrap().receive(frame);
+            Vec::new()
+        });
+        report_status(&controller, move || simulator.lock().unwrap().status());
+
+        let client = Client::from_transport(transport).with_min_frame_interval(Duration::from_millis(1));
+        let total = recipe().to_commands().unwrap().len();
+
+        let mut progress = Vec::new();
+        client.upload_recipe(&recipe(), &upload_options(), |update| progress.push(update)).await.unwrap();
+
+        assert_eq!(
+            vec![
+                UploadProgress::Sending {
+                    attempt: 1,
+                    sent: total,
+                    total,
+                },
+                UploadProgress::Verifying {
+                    attempt: 1
+                },
+            ],
+            progress[progress.len() - 2..].to_vec()
+        );
+
+        // Invalid recipes are rejected before anything is sent
+        controller.take_written();
+        let result = client.upload_recipe(&Recipe::default(), &upload_options(), |_| {}).await;
+
+        assert!(matches!(result, Err(Error::Invalid(_))));
+        assert!(controller.take_written().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancels_sessions_that_dont_load() {
+        let (transport, controller) = LoopbackTransport::new();
+        let client = Client::from_transport(transport).with_min_frame_interval(Duration::from_millis(1));
+
+        // A controller that never reports its status has its session cancelled before
+        // each attempt, and after the last
+        let result = client.upload_recipe(&recipe(), &upload_options(), |_| {}).await;
+        let cancel = Command::Disconnect(DisconnectOption::CancelSession).to_vec().unwrap();
+
+        assert!(matches!(
+            result,
+            Err(Error::NotLoaded {
+                attempts: 2
+            })
+        ));
+        assert_eq!(vec![cancel.clone(), cancel.clone(), cancel], controller.take_written());
+    }
+
+    #[tokio::test]
+    async fn doesnt_mistake_a_running_session_for_the_recipe() {
+        let (transport, controller) = LoopbackTransport::new();
+
+        // The controller is already running a recipe, and ignores everything it's sent
+        report_status(&controller, || {
+            vec![Notification::Status1(Status1 {
+                heat_active: true,
+                pump_active: true,
+                auto_mode_active: true,
+                step_ramp_active: false,
+                interaction_mode_active: false,
+                interaction_code: InteractionCode::None,
+                step_number: 1,
+                delayed_heat_mode_active: false,
+            })]
+        });
+
+        let client = Client::from_transport(transport).with_min_frame_interval(Duration::from_millis(1));
+        let result = client.upload_recipe(&recipe(), &upload_options(), |_| {}).await;
+
+        assert!(matches!(
+            result,
+            Err(Error::NotLoaded {
+                attempts: 2
+            })
+        ));
+    }
+}
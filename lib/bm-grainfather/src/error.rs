@@ -0,0 +1,67 @@
+use crate::transport::TransportError;
+use crate::{CommandEncodeError, RecipeProblem};
+
+use std::fmt;
+use std::time::Duration;
+
+/// Possible errors encountered working with a controller through a [client](crate::Client).
+#[derive(Debug)]
+pub enum Error {
+    /// The controller isn't connected, and may yet reconnect.
+    NotConnected,
+
+    /// The frames couldn't be carried to or from the controller.
+    Transport(TransportError),
+
+    /// The client's command queue already holds the given number of commands, so no
+    /// more are accepted until some have been written.
+    QueueFull(usize),
+
+    /// The controller didn't reply within the given time.
+    Timeout(Duration),
+
+    /// The recipe was rejected before any of it was sent, see [validate](crate::Recipe::validate).
+    Invalid(Vec<RecipeProblem>),
+
+    /// The command or recipe can't be encoded into frames.
+    Encode(CommandEncodeError),
+
+    /// The controller didn't report that it loaded the recipe after the given number
+    /// of attempts.
+    NotLoaded {
+        attempts: u32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NotConnected => write!(f, "The grainfather isn't connected"),
+            Self::Transport(err) => write!(f, "Unable to communicate with the grainfather: {:?}", err),
+            Self::QueueFull(depth) => write!(f, "The grainfather has {} commands waiting to be sent", depth),
+            Self::Timeout(timeout) => write!(f, "The grainfather didn't reply within {:?}", timeout),
+            Self::Invalid(problems) => write!(f, "The recipe has {} problems", problems.len()),
+            Self::Encode(err) => write!(f, "Unable to encode for the grainfather: {:?}", err),
+            Self::NotLoaded {
+                attempts,
+            } => write!(f, "The grainfather didn't load the recipe after {} attempts", attempts),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<TransportError> for Error {
+    fn from(other: TransportError) -> Self {
+        match other {
+            TransportError::NotConnected => Self::NotConnected,
+            other => Self::Transport(other),
+        }
+    }
+}
+
+impl From<CommandEncodeError> for Error {
+    fn from(other: CommandEncodeError) -> Self {
+        Self::Encode(other)
+    }
+}
@@ -1,12 +1,13 @@
 //! This crate provides facilities for communicating with a Grainfather controller.
 //!
 //! There are broadly 3 components to this library, protocol parsing/unparsing,
-//! bluetooth helpers, and an implementation of a client using the btleplug
-//! bluetooth library.
+//! bluetooth helpers, and a client which works over any of a number of transports.
 //!
 //! [Command](crate::Command), [Notification](crate::Notification), and
 //! [Recipe](crate::Recipe) are the three principal protocol-level types
-//! and support the parsing/unparsing of those entities.
+//! and support the parsing/unparsing of those entities. A [BrewSession](crate::BrewSession)
+//! combines a recipe with the notifications received while it runs to track the
+//! [phase](crate::BrewPhase) of the brew day.
 //!
 //! The bluetooth helpers include the [service id](crate::SERVICE_ID), and
 //! [read](crate::CHARACTERISTIC_ID_READ)/[write](crate::CHARACTERISTIC_ID_WRITE)
@@ -14,17 +15,45 @@
 //! can be used to see if the service id is contained within an Extended Information
 //! Report returned by a bluetooth library.
 //!
-//! Finally, there's a `btleplug` feature (turned on by default), which makes
-//! a [client](crate::btleplug::Client) available which wraps a
-//! [`btleplug::api::Peripheral`](::btleplug::api::Peripheral) and exposes
-//! an API for interacting with the Grainfather Controller in terms of commands,
-//! notifications, and recipes.
+//! Finally, the [client](crate::Client) exposes an API for interacting with the
+//! Grainfather Controller in terms of commands, notifications, and recipes. It
+//! carries frames over a [transport](crate::transport), there's a `btleplug` feature
+//! (turned on by default), which provides a transport that wraps a
+//! [`btleplug::api::Peripheral`](::btleplug::api::Peripheral), and others for testing
+//! and for reaching a controller through a remote gateway.
+//!
+//! The client can also [capture](crate::capture) the raw frames it exchanges with the
+//! controller, and replay captures in place of a real controller, or [trace](crate::trace)
+//! the decoded conversation as it happens.
+//!
+//! A [simulated controller](crate::simulator::Simulator) is also provided, which
+//! responds to commands and recipes in the same way as the real controller, and
+//! can stand in for it when developing without hardware.
 
-// TODO: review temperature units
 pub mod calc;
 
 mod proto;
 pub use proto::*;
 
+mod session;
+pub use session::*;
+
 mod bluetooth;
 pub use bluetooth::*;
+
+mod error;
+pub use error::*;
+
+mod client;
+pub use client::*;
+
+mod queue;
+pub use queue::*;
+
+pub mod transport;
+
+pub mod capture;
+
+pub mod trace;
+
+pub mod simulator;
@@ -1,11 +1,16 @@
 use std::{convert::TryFrom, str::FromStr};
 
+mod fields;
+
 mod command;
 pub use command::*;
 
 mod notification;
 pub use notification::*;
 
+mod framer;
+pub use framer::*;
+
 mod recipe;
 pub use recipe::*;
 
@@ -1,8 +1,10 @@
+use super::fields::{FieldError, Fields};
 use super::*;
+use bm_units::{celsius, CentiCelsius};
 
-pub(crate) const COMMAND_LEN: usize = 19;
+pub const COMMAND_LEN: usize = 19;
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Delay {
     Minutes(u32),
@@ -10,7 +12,7 @@ pub enum Delay {
 }
 
 /// Indicates the type of disconnection to perform.
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum DisconnectOption {
     /// Disconnects the controller, canceling the session (recipe) if one is active.
@@ -23,7 +25,7 @@ pub enum DisconnectOption {
     AutomaticMode,
 }
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Command {
     Reset,
@@ -60,8 +62,8 @@ pub enum Command {
 
     IncrementTargetTemperature,
     DecrementTargetTemperature,
-    SetTargetTemperature(f64),
-    SetLocalBoilTemperature(f64),
+    SetTargetTemperature(#[serde(with = "celsius")] CentiCelsius),
+    SetLocalBoilTemperature(#[serde(with = "celsius")] CentiCelsius),
 
     /// Dismisses the active alert - e.g. the heat sparge water alert, or
     /// a boil addition alert. These alerts can also be dismissed by
@@ -82,7 +84,8 @@ pub enum Command {
 
     UpdateStep {
         step_number: StepNumber,
-        temperature: f64,
+        #[serde(with = "celsius")]
+        temperature: CentiCelsius,
 
         // TODO: is this actually minutes?
         time_minutes: u8,
@@ -110,8 +113,17 @@ pub enum Command {
     SetSpargeAlertModeActive(bool),
 }
 
+/// Possible errors encountered when encoding a command.
+#[derive(Debug)]
+pub enum CommandEncodeError {
+    /// The encoded command was longer than the 19 bytes available in a frame.
+    TooLong(usize),
+}
+
 impl Command {
-    pub fn to_vec(&self) -> Vec<u8> {
+    /// Encodes the command into a frame, failing if the command's values can't
+    /// be represented in a single frame.
+    pub fn to_vec(&self) -> Result<Vec<u8>, CommandEncodeError> {
         let mut output = String::with_capacity(COMMAND_LEN);
 
         match self {
@@ -215,13 +227,13 @@ impl Command {
 
             Self::SetTargetTemperature(temp) => {
                 output.push('$');
-                output.push_str(temp.to_string().as_ref());
+                output.push_str(format_temperature(*temp).as_ref());
                 output.push(',');
             }
 
             Self::SetLocalBoilTemperature(temp) => {
                 output.push('E');
-                output.push_str(temp.to_string().as_ref());
+                output.push_str(format_temperature(*temp).as_ref());
                 output.push(',');
             }
 
@@ -264,7 +276,7 @@ impl Command {
                 output.push('a');
                 output.push_str(step_number.to_string().as_ref());
                 output.push(',');
-                output.push_str(temperature.to_string().as_ref());
+                output.push_str(format_temperature(*temperature).as_ref());
                 output.push(',');
                 output.push_str(time_minutes.to_string().as_ref());
                 output.push(',');
@@ -377,10 +389,299 @@ impl Command {
     }
 }
 
-pub(crate) fn finish_command(mut command_str: String) -> Vec<u8> {
-    for _ in 0..(COMMAND_LEN - command_str.len()) {
+/// Possible errors encountered when parsing a command frame.
+#[derive(Debug)]
+pub enum CommandConvertError {
+    /// The command was not valid UTF-8.
+    InvalidUtf8(std::str::Utf8Error),
+
+    /// The frame was not the 19 bytes expected of a command.
+    InvalidLength(usize),
+
+    /// The frame did not contain a type character.
+    Empty,
+
+    /// The frame's type doesn't correspond to a known command.
+    UnknownType(char),
+
+    /// The command of the given type was missing the field at the given (zero-based) index.
+    MissingField {
+        r#type: char,
+        index: usize,
+    },
+
+    /// The field at the given (zero-based) index of a command of the given type
+    /// could not be parsed.
+    InvalidField {
+        r#type: char,
+        index: usize,
+        value: String,
+    },
+}
+
+impl From<FieldError> for CommandConvertError {
+    fn from(other: FieldError) -> Self {
+        match other {
+            FieldError::Missing {
+                r#type,
+                index,
+            } => Self::MissingField {
+                r#type,
+                index,
+            },
+
+            FieldError::Invalid {
+                r#type,
+                index,
+                value,
+            } => Self::InvalidField {
+                r#type,
+                index,
+                value,
+            },
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Command {
+    type Error = CommandConvertError;
+
+    fn try_from(frame: &[u8]) -> Result<Self, Self::Error> {
+        if frame.len() != COMMAND_LEN {
+            return Err(Self::Error::InvalidLength(frame.len()));
+        }
+
+        let cdata = std::str::from_utf8(frame).map_err(Self::Error::InvalidUtf8)?.trim_end_matches(' ');
+        let mut cdata_chars = cdata.chars();
+        let cdata_type = cdata_chars.next().ok_or(Self::Error::Empty)?;
+
+        // The sparge progress command is the only one with a two character prefix
+        let cdata_rest = if cdata_type == 'b' {
+            cdata_chars.as_str().strip_prefix('$').ok_or(Self::Error::UnknownType(cdata_type))?
+        } else {
+            cdata_chars.as_str()
+        };
+
+        let mut cdata_fields = Fields::new(cdata_type, cdata_rest);
+
+        match cdata_type {
+            'Z' => Ok(Self::Reset),
+            'X' => Ok(Self::GetFirmwareVersion),
+            'g' => Ok(Self::GetVoltageAndUnits),
+            'M' => Ok(Self::GetBoilTemperature),
+            'H' => Ok(Self::ToggleHeatActive),
+            'K' => Ok(Self::SetHeatActive(cdata_fields.next_flag()?)),
+            'P' => Ok(Self::TogglePumpActive),
+            'L' => Ok(Self::SetPumpActive(cdata_fields.next_flag()?)),
+
+            'B' => {
+                let minutes = cdata_fields.next_parse()?;
+                let seconds = cdata_fields.next_parse()?;
+                Ok(Self::EnableDelayedHeatTimer {
+                    minutes,
+                    seconds,
+                })
+            }
+
+            'C' => Ok(Self::CancelActiveTimer),
+
+            'W' => {
+                let minutes = cdata_fields.next_parse()?;
+                let seconds = cdata_fields.next_parse()?;
+                Ok(Self::UpdateActiveTimer(Delay::MinutesSeconds(minutes, seconds)))
+            }
+
+            'S' => Ok(Self::UpdateActiveTimer(Delay::Minutes(cdata_fields.next_parse()?))),
+            'G' => Ok(Self::PauseOrResumeActiveTimer),
+            'U' => Ok(Self::IncrementTargetTemperature),
+            'D' => Ok(Self::DecrementTargetTemperature),
+            '$' => Ok(Self::SetTargetTemperature(CentiCelsius::from_celsius(cdata_fields.next_parse()?))),
+            'E' => Ok(Self::SetLocalBoilTemperature(CentiCelsius::from_celsius(cdata_fields.next_parse()?))),
+            'A' => Ok(Self::DismissAlert),
+            'F' => Ok(Self::CancelOrFinishSession),
+            'T' => Ok(Self::PressSet),
+            'V' => Ok(Self::DisableSpargeWaterAlert),
+            '!' => Ok(Self::ResetRecipeInterrupted),
+            'b' => Ok(Self::SetSpargeProgress(cdata_fields.next_parse()?)),
+
+            'a' => {
+                let step_number = cdata_fields.next_parse()?;
+                let temperature = CentiCelsius::from_celsius(cdata_fields.next_parse()?);
+                let time_minutes = cdata_fields.next_parse()?;
+                Ok(Self::UpdateStep {
+                    step_number,
+                    temperature,
+                    time_minutes,
+                })
+            }
+
+            'N' => {
+                let step_number = cdata_fields.next_parse()?;
+                let can_edit_minutes = cdata_fields.next_parse()?;
+                let time_left_minutes = cdata_fields.next_parse()?;
+                let time_left_seconds = cdata_fields.next_parse()?;
+                let skip_ramp = cdata_fields.next_flag()?;
+                let disable_add_grain = cdata_fields.next_flag()?;
+                Ok(Self::SkipToStep {
+                    step_number,
+                    can_edit_minutes,
+                    time_left_minutes,
+                    time_left_seconds,
+                    skip_ramp,
+                    disable_add_grain,
+                })
+            }
+
+            'I' => Ok(Self::InteractionComplete),
+            'c' => Ok(Self::SkipToInteraction(cdata_fields.next_parse()?)),
+
+            'Q' => match cdata_fields.next_str()? {
+                "0" => Ok(Self::Disconnect(DisconnectOption::ManualMode)),
+                "1" => Ok(Self::Disconnect(DisconnectOption::CancelSession)),
+                "2" => Ok(Self::Disconnect(DisconnectOption::AutomaticMode)),
+                other => Err(cdata_fields.invalid(0, other).into()),
+            },
+
+            'd' => Ok(Self::SetSpargeCounterActive(cdata_fields.next_flag()?)),
+            'e' => Ok(Self::SetBoilControlActive(cdata_fields.next_flag()?)),
+            'f' => Ok(Self::SetManualPowerControlActive(cdata_fields.next_flag()?)),
+            'h' => Ok(Self::SetSpargeAlertModeActive(cdata_fields.next_flag()?)),
+
+            other => Err(Self::Error::UnknownType(other)),
+        }
+    }
+}
+
+/// Formats a temperature with the single decimal place used by the controller.
+pub(crate) fn format_temperature(temperature: CentiCelsius) -> String {
+    format!("{:.1}", temperature.celsius())
+}
+
+pub(crate) fn finish_command(mut command_str: String) -> Result<Vec<u8>, CommandEncodeError> {
+    if command_str.len() > COMMAND_LEN {
+        return Err(CommandEncodeError::TooLong(command_str.len()));
+    }
+
+    while command_str.len() < COMMAND_LEN {
         command_str.push(' ');
     }
 
-    command_str.into()
+    Ok(command_str.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bm_units::CentiCelsius;
+
+    fn all_commands() -> Vec<Command> {
+        vec![
+            Command::Reset,
+            Command::GetFirmwareVersion,
+            Command::GetVoltageAndUnits,
+            Command::GetBoilTemperature,
+            Command::ToggleHeatActive,
+            Command::SetHeatActive(true),
+            Command::SetHeatActive(false),
+            Command::TogglePumpActive,
+            Command::SetPumpActive(true),
+            Command::SetPumpActive(false),
+            Command::EnableDelayedHeatTimer {
+                minutes: 120,
+                seconds: 30,
+            },
+            Command::CancelActiveTimer,
+            Command::UpdateActiveTimer(Delay::Minutes(45)),
+            Command::UpdateActiveTimer(Delay::MinutesSeconds(2, 59)),
+            Command::PauseOrResumeActiveTimer,
+            Command::IncrementTargetTemperature,
+            Command::DecrementTargetTemperature,
+            Command::SetTargetTemperature(CentiCelsius::new(6550)),
+            Command::SetLocalBoilTemperature(CentiCelsius::new(9950)),
+            Command::DismissAlert,
+            Command::CancelOrFinishSession,
+            Command::PressSet,
+            Command::DisableSpargeWaterAlert,
+            Command::ResetRecipeInterrupted,
+            Command::Disconnect(DisconnectOption::ManualMode),
+            Command::Disconnect(DisconnectOption::CancelSession),
+            Command::Disconnect(DisconnectOption::AutomaticMode),
+            Command::SetSpargeProgress(5),
+            Command::UpdateStep {
+                step_number: 2,
+                temperature: CentiCelsius::from_degrees(72),
+                time_minutes: 15,
+            },
+            Command::SkipToStep {
+                step_number: 3,
+                can_edit_minutes: 0,
+                time_left_minutes: 10,
+                time_left_seconds: 0,
+                skip_ramp: true,
+                disable_add_grain: false,
+            },
+            Command::InteractionComplete,
+            Command::SkipToInteraction(InteractionCode::BoilReached),
+            Command::SkipToInteraction(InteractionCode::Dismiss),
+            Command::SetSpargeCounterActive(true),
+            Command::SetBoilControlActive(false),
+            Command::SetManualPowerControlActive(true),
+            Command::SetSpargeAlertModeActive(false),
+        ]
+    }
+
+    #[test]
+    fn round_trips_all_commands() {
+        for command in all_commands() {
+            let frame = command.to_vec().unwrap();
+            assert_eq!(COMMAND_LEN, frame.len());
+
+            let decoded = Command::try_from(frame.as_ref())
+                .unwrap_or_else(|err| panic!("Unable to decode {:?}: {:?}", command, err));
+
+            assert_eq!(command, decoded);
+        }
+    }
+
+    #[test]
+    fn encodes_temperatures_with_fixed_precision() {
+        let command = Command::SetTargetTemperature(CentiCelsius::new(6512));
+        assert_eq!(&b"$65.1,             "[..], command.to_vec().unwrap().as_slice());
+
+        let command = Command::SetLocalBoilTemperature(CentiCelsius::from_degrees(99));
+        assert_eq!(&b"E99.0,             "[..], command.to_vec().unwrap().as_slice());
+    }
+
+    #[test]
+    fn rejects_unencodable_commands() {
+        assert!(matches!(
+            Command::SetTargetTemperature(CentiCelsius::new(isize::MAX)).to_vec(),
+            Err(CommandEncodeError::TooLong(_))
+        ));
+        assert!(matches!(
+            Command::SkipToInteraction(InteractionCode::Other("a-very-long-interaction".into())).to_vec(),
+            Err(CommandEncodeError::TooLong(25))
+        ));
+    }
+
+    #[test]
+    fn decodes_padded_frames() {
+        assert_eq!(Command::SetHeatActive(true), Command::try_from(&b"K1,                "[..]).unwrap());
+        assert_eq!(Command::SetSpargeProgress(5), Command::try_from(&b"b$5,               "[..]).unwrap());
+    }
+
+    #[test]
+    fn rejects_bad_frames() {
+        assert!(matches!(Command::try_from(&b"K1,"[..]), Err(CommandConvertError::InvalidLength(3))));
+        assert!(matches!(Command::try_from(&b"                   "[..]), Err(CommandConvertError::Empty)));
+        assert!(matches!(Command::try_from(&b"R60,3,13.25,14.64, "[..]), Err(CommandConvertError::UnknownType('R'))));
+        assert!(matches!(
+            Command::try_from(&b"Q3,                "[..]),
+            Err(CommandConvertError::InvalidField {
+                r#type: 'Q',
+                index: 0,
+                ..
+            })
+        ));
+    }
 }
@@ -0,0 +1,81 @@
+//! Helpers for walking the comma separated fields of command and notification frames.
+
+use std::str::FromStr;
+
+/// Describes a field that couldn't be read from a frame of the given type.
+#[derive(Debug)]
+pub(crate) enum FieldError {
+    Missing {
+        r#type: char,
+        index: usize,
+    },
+    Invalid {
+        r#type: char,
+        index: usize,
+        value: String,
+    },
+}
+
+/// Walks the comma separated fields of a frame, producing errors which identify
+/// the frame type and (zero-based) field index on failure.
+pub(crate) struct Fields<'a> {
+    r#type: char,
+    index: usize,
+    fields: std::str::Split<'a, char>,
+}
+
+impl<'a> Fields<'a> {
+    pub fn new(r#type: char, data: &'a str) -> Self {
+        Self {
+            r#type,
+            index: 0,
+            fields: data.split(','),
+        }
+    }
+
+    pub fn next_str(&mut self) -> Result<&'a str, FieldError> {
+        let index = self.index;
+
+        let field = self.fields.next().ok_or(FieldError::Missing {
+            r#type: self.r#type,
+            index,
+        })?;
+
+        self.index += 1;
+
+        Ok(field)
+    }
+
+    pub fn next_parse<T>(&mut self) -> Result<T, FieldError>
+    where
+        T: FromStr,
+    {
+        let index = self.index;
+        let field = self.next_str()?;
+
+        field.parse().map_err(|_| self.invalid(index, field))
+    }
+
+    pub fn next_bool(&mut self) -> Result<bool, FieldError> {
+        self.next_parse::<u8>().map(|value| value == 1)
+    }
+
+    /// Reads a field which must be exactly `0` or `1`.
+    pub fn next_flag(&mut self) -> Result<bool, FieldError> {
+        let index = self.index;
+
+        match self.next_str()? {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            other => Err(self.invalid(index, other)),
+        }
+    }
+
+    pub fn invalid(&self, index: usize, value: &str) -> FieldError {
+        FieldError::Invalid {
+            r#type: self.r#type,
+            index,
+            value: value.to_string(),
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use super::*;
+
+/// Counters describing how well the notification data received from a controller
+/// has been framed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FramingStats {
+    /// The number of notifications decoded.
+    pub notifications: u64,
+
+    /// The number of bytes discarded because they didn't belong to a valid frame.
+    pub discarded_bytes: u64,
+
+    /// The number of times the framer lost track of the frame boundaries, and had to
+    /// search for the start of the next frame.
+    pub resyncs: u64,
+}
+
+/// Splits the notification data received from a controller into notifications.
+///
+/// Notification data can arrive in chunks of any size, and the framer copes with
+/// frames being split across chunks. If bytes are dropped or corrupted, the frame
+/// affected is discarded and the framer searches for the start of the next frame
+/// by looking for a known type character followed by fields of the right shape, so
+/// that a single bad byte doesn't misalign every frame that follows.
+#[derive(Debug)]
+pub struct NotificationFramer {
+    buf: Vec<u8>,
+    synced: bool,
+    stats: FramingStats,
+}
+
+/// How well some bytes match the shape of a notification frame.
+#[derive(Debug, Eq, PartialEq)]
+enum Shape {
+    /// The bytes can't be the start of a frame.
+    Invalid,
+
+    /// The bytes could be the start of a frame, but more are needed to be sure.
+    Incomplete,
+
+    /// The bytes are a whole frame of the right shape.
+    Complete,
+}
+
+impl NotificationFramer {
+    /// The type characters of the notifications that the controller is known to emit.
+    const KNOWN_TYPES: &'static [u8] = b"ABCEFITVWXY";
+
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::with_capacity(NOTIFICATION_LEN * 8),
+            synced: true,
+            stats: FramingStats::default(),
+        }
+    }
+
+    /// Adds a chunk of notification data, returning any notifications it completes.
+    pub fn push(&mut self, data: &[u8]) -> Vec<Notification> {
+        self.push_frames(data).into_iter().map(|(notification, _)| notification).collect()
+    }
+
+    /// Adds a chunk of notification data, returning any notifications it completes
+    /// alongside the frames they were decoded from.
+    pub fn push_frames(&mut self, data: &[u8]) -> Vec<(Notification, Vec<u8>)> {
+        self.buf.extend_from_slice(data);
+
+        let mut notifications = Vec::new();
+
+        while !self.buf.is_empty() {
+            let candidate = &self.buf[..self.buf.len().min(NOTIFICATION_LEN)];
+
+            match Self::shape(candidate, self.synced) {
+                Shape::Incomplete => break,
+
+                Shape::Complete => {
+                    if let Ok(notification) = Notification::try_from(candidate) {
+                        notifications.push((notification, candidate.to_vec()));
+
+                        self.buf.drain(..NOTIFICATION_LEN);
+                        self.synced = true;
+                        self.stats.notifications += 1;
+                        continue;
+                    }
+                }
+
+                Shape::Invalid => {}
+            }
+
+            if self.synced {
+                self.synced = false;
+                self.stats.resyncs += 1;
+            }
+
+            // Skip to the next byte that could start a frame
+            let skip = self.buf[1..]
+                .iter()
+                .position(|byte| Self::KNOWN_TYPES.contains(byte))
+                .map(|position| position + 1)
+                .unwrap_or_else(|| self.buf.len());
+
+            self.buf.drain(..skip);
+            self.stats.discarded_bytes += skip as u64;
+        }
+
+        notifications
+    }
+
+    pub fn stats(&self) -> FramingStats {
+        self.stats
+    }
+
+    /// Checks whether the given bytes match the shape of a frame, a type character
+    /// followed by comma terminated fields, then space padding.
+    ///
+    /// Frames of an unknown type are only accepted when the framer is in sync, as
+    /// otherwise they're indistinguishable from corrupt data.
+    fn shape(bytes: &[u8], synced: bool) -> Shape {
+        let (r#type, body) = match bytes.split_first() {
+            Some((r#type, body)) => (*r#type, body),
+            None => return Shape::Incomplete,
+        };
+
+        let known = Self::KNOWN_TYPES.contains(&r#type);
+
+        if !(known || synced && r#type.is_ascii_graphic()) {
+            return Shape::Invalid;
+        }
+
+        let padding = body.iter().position(|byte| *byte == b' ').unwrap_or(body.len());
+        let (fields, padding) = body.split_at(padding);
+
+        let valid_field_byte = |byte: &u8| byte.is_ascii_alphanumeric() || b",.-".contains(byte);
+
+        if !fields.iter().all(valid_field_byte) || !padding.iter().all(|byte| *byte == b' ') {
+            return Shape::Invalid;
+        }
+
+        if bytes.len() < NOTIFICATION_LEN {
+            return Shape::Incomplete;
+        }
+
+        if !known {
+            return Shape::Complete;
+        }
+
+        let field_count = fields.iter().filter(|byte| **byte == b',').count();
+
+        if fields.last().map(|byte| *byte == b',').unwrap_or(true) && field_count == Self::field_count(r#type) {
+            Shape::Complete
+        } else {
+            Shape::Invalid
+        }
+    }
+
+    /// The number of fields in a frame of the given known type.
+    fn field_count(r#type: u8) -> usize {
+        match r#type {
+            b'X' | b'V' => 2,
+            b'T' => 4,
+            b'Y' => 8,
+            b'W' => 6,
+            b'I' | b'C' | b'F' => 1,
+            _ => 0,
+        }
+    }
+}
+
+impl Default for NotificationFramer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const FRAMES: &[&[u8]] = &[
+        b"X65,21.5,        ",
+        b"T1,60,60,0,      ",
+        b"Y1,0,1,0,1,C,3,0,",
+        b"W100,0,0,0,0,0,  ",
+        b"IC,              ",
+        b"F1.2.3,          ",
+        b"A",
+    ];
+
+    /// The frame with the given index, padded to length.
+    fn frame(index: usize) -> Vec<u8> {
+        let mut frame = FRAMES[index].to_vec();
+        frame.resize(NOTIFICATION_LEN, b' ');
+        frame
+    }
+
+    fn decode(index: usize) -> Notification {
+        Notification::try_from(frame(index).as_slice()).unwrap()
+    }
+
+    #[test]
+    fn frames_fragmented_data() {
+        let mut framer = NotificationFramer::new();
+        let data = [frame(0), frame(1), frame(2)].concat();
+
+        let notifications = data.iter().flat_map(|byte| framer.push(&[*byte])).collect::<Vec<_>>();
+
+        assert_eq!(vec![decode(0), decode(1), decode(2)], notifications);
+        assert_eq!(
+            FramingStats {
+                notifications: 3,
+                discarded_bytes: 0,
+                resyncs: 0,
+            },
+            framer.stats()
+        );
+    }
+
+    #[test]
+    fn resyncs_after_a_dropped_byte() {
+        let mut framer = NotificationFramer::new();
+        let mut data = [frame(2), frame(0), frame(1)].concat();
+
+        // Drop a byte from the middle of the first frame, the rest of which is discarded
+        data.remove(4);
+
+        assert_eq!(vec![decode(0), decode(1)], framer.push(&data));
+        assert_eq!(
+            FramingStats {
+                notifications: 2,
+                discarded_bytes: 16,
+                resyncs: 1,
+            },
+            framer.stats()
+        );
+    }
+
+    #[test]
+    fn resyncs_after_extra_bytes() {
+        let mut framer = NotificationFramer::new();
+        let data = [frame(3), b"\0Y1".to_vec(), frame(4), frame(5)].concat();
+
+        assert_eq!(vec![decode(3), decode(4), decode(5)], framer.push(&data));
+        assert_eq!(3, framer.stats().discarded_bytes);
+        assert_eq!(1, framer.stats().resyncs);
+    }
+
+    #[test]
+    fn accepts_unknown_types_only_in_sync() {
+        let mut framer = NotificationFramer::new();
+        let unknown = b"Q1,2,            ".to_vec();
+
+        assert_eq!(1, framer.push(&unknown).len());
+        assert_eq!(vec![decode(0)], framer.push(&[b"\xff".to_vec(), unknown, frame(0)].concat()));
+        assert_eq!(NOTIFICATION_LEN as u64 + 1, framer.stats().discarded_bytes);
+    }
+
+    proptest! {
+        #[test]
+        fn frames_arbitrarily_chunked_data(
+            indexes in prop::collection::vec(0..FRAMES.len(), 0..20),
+            chunk_len in 1..40usize,
+        ) {
+            let mut framer = NotificationFramer::new();
+            let data = indexes.iter().flat_map(|index| frame(*index)).collect::<Vec<_>>();
+
+            let notifications = data.chunks(chunk_len).flat_map(|chunk| framer.push(chunk)).collect::<Vec<_>>();
+
+            prop_assert_eq!(indexes.iter().map(|index| decode(*index)).collect::<Vec<_>>(), notifications);
+            prop_assert_eq!(0, framer.stats().discarded_bytes);
+        }
+
+        #[test]
+        fn recovers_every_frame_after_noise(
+            frames in prop::collection::vec((0..FRAMES.len(), prop::collection::vec(0..0x20u8, 0..20)), 1..20),
+            chunk_len in 1..40usize,
+        ) {
+            let mut framer = NotificationFramer::new();
+
+            // Noise between frames never looks like the start of a frame
+            let data = frames.iter().flat_map(|(index, noise)| [noise.clone(), frame(*index)].concat()).collect::<Vec<_>>();
+            let noise_len = frames.iter().map(|(_, noise)| noise.len() as u64).sum::<u64>();
+
+            let notifications = data.chunks(chunk_len).flat_map(|chunk| framer.push(chunk)).collect::<Vec<_>>();
+
+            prop_assert_eq!(frames.iter().map(|(index, _)| decode(*index)).collect::<Vec<_>>(), notifications);
+            prop_assert_eq!(noise_len, framer.stats().discarded_bytes);
+        }
+    }
+}
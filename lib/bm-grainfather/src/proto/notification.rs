@@ -1,10 +1,16 @@
 pub mod notifications;
 use notifications::*;
 
+use super::fields::{FieldError, Fields};
 use super::*;
+use bm_units::CentiCelsius;
+use std::fmt::Write;
+
+/// The length of a single notification frame emitted by the controller.
+pub const NOTIFICATION_LEN: usize = 17;
 
 /// Represents the Grainfather controller's supported power supply.
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum Voltage {
     V110,
@@ -15,7 +21,7 @@ pub enum Voltage {
 ///
 /// Note that the units used in commands, notifications, and recipes always use
 /// degrees celsius.
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum Units {
     Fahrenheit,
@@ -23,7 +29,7 @@ pub enum Units {
 }
 
 /// Represents a notification received asynchronously from the Grainfather controller.
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Notification {
     /// Indicates the current and target temperature measured by the controller.
@@ -65,9 +71,193 @@ pub enum Notification {
     Other(Other),
 }
 
+/// Possible errors encountered when parsing a notification received from the controller.
 #[derive(Debug)]
 pub enum NotificationConvertError {
+    /// The notification was not valid UTF-8.
     InvalidUtf8(std::str::Utf8Error),
+
+    /// The notification did not contain a type character.
+    Empty,
+
+    /// The notification of the given type was missing the field at the given (zero-based) index.
+    MissingField {
+        r#type: char,
+        index: usize,
+    },
+
+    /// The field at the given (zero-based) index of a notification of the given type
+    /// could not be parsed.
+    InvalidField {
+        r#type: char,
+        index: usize,
+        value: String,
+    },
+}
+
+/// Possible errors encountered when encoding a notification.
+#[derive(Debug)]
+pub enum NotificationEncodeError {
+    /// The encoded notification was longer than the 17 bytes available in a frame.
+    TooLong(usize),
+}
+
+impl Notification {
+    /// Encodes the notification into the frame that the controller would emit for it,
+    /// this is the inverse of parsing a notification from a frame.
+    pub fn to_vec(&self) -> Result<Vec<u8>, NotificationEncodeError> {
+        let mut output = String::with_capacity(NOTIFICATION_LEN);
+
+        match self {
+            Self::PromptBoilAddition(PromptBoilAddition) => output.push('A'),
+
+            Self::PromptSpargeWater(PromptSpargeWater) => output.push('B'),
+
+            Self::TemperatureReached(TemperatureReached) => output.push('E'),
+
+            Self::Temp(Temp {
+                desired,
+                current,
+            }) => {
+                write!(output, "X{},{},", desired.celsius(), current.celsius()).unwrap();
+            }
+
+            Self::Timer(Timer {
+                active,
+                remaining_minutes,
+                remaining_seconds,
+                total_start_time,
+            }) => {
+                write!(
+                    output,
+                    "T{},{},{},{},",
+                    u8::from(*active),
+                    remaining_minutes,
+                    total_start_time,
+                    remaining_seconds
+                )
+                .unwrap();
+            }
+
+            Self::Status1(Status1 {
+                heat_active,
+                pump_active,
+                auto_mode_active,
+                step_ramp_active,
+                interaction_mode_active,
+                interaction_code,
+                step_number,
+                delayed_heat_mode_active,
+            }) => {
+                write!(
+                    output,
+                    "Y{},{},{},{},{},{},{},{},",
+                    u8::from(*heat_active),
+                    u8::from(*pump_active),
+                    u8::from(*auto_mode_active),
+                    u8::from(*step_ramp_active),
+                    u8::from(*interaction_mode_active),
+                    interaction_code.to_string(),
+                    step_number,
+                    u8::from(*delayed_heat_mode_active)
+                )
+                .unwrap();
+            }
+
+            Self::Status2(Status2 {
+                heat_power_output_percentage,
+                timer_paused,
+                step_mash_mode,
+                recipe_interrupted,
+                manual_power_mode,
+                sparge_water_alert_displayed,
+            }) => {
+                write!(
+                    output,
+                    "W{},{},{},{},{},{},",
+                    heat_power_output_percentage,
+                    u8::from(*timer_paused),
+                    u8::from(*step_mash_mode),
+                    u8::from(*recipe_interrupted),
+                    u8::from(*manual_power_mode),
+                    u8::from(*sparge_water_alert_displayed)
+                )
+                .unwrap();
+            }
+
+            Self::Interaction(Interaction {
+                interaction_code,
+            }) => {
+                write!(output, "I{},", interaction_code.to_string()).unwrap();
+            }
+
+            Self::Boil(Boil {
+                boil_temperature,
+            }) => {
+                write!(output, "C{},", boil_temperature.celsius()).unwrap();
+            }
+
+            Self::FirmwareVersion(FirmwareVersion {
+                firmware_version,
+            }) => {
+                write!(output, "F{},", firmware_version).unwrap();
+            }
+
+            Self::VoltageAndUnits(VoltageAndUnits {
+                voltage,
+                units,
+            }) => {
+                write!(output, "V{},{},", u8::from(*voltage == Voltage::V110), u8::from(*units == Units::Celsius))
+                    .unwrap();
+            }
+
+            Self::Other(Other {
+                r#type,
+                data,
+            }) => {
+                output.push(*r#type);
+                output.push_str(data);
+            }
+        }
+
+        finish_notification(output)
+    }
+}
+
+fn finish_notification(mut notification_str: String) -> Result<Vec<u8>, NotificationEncodeError> {
+    if notification_str.len() > NOTIFICATION_LEN {
+        return Err(NotificationEncodeError::TooLong(notification_str.len()));
+    }
+
+    while notification_str.len() < NOTIFICATION_LEN {
+        notification_str.push(' ');
+    }
+
+    Ok(notification_str.into())
+}
+
+impl From<FieldError> for NotificationConvertError {
+    fn from(other: FieldError) -> Self {
+        match other {
+            FieldError::Missing {
+                r#type,
+                index,
+            } => Self::MissingField {
+                r#type,
+                index,
+            },
+
+            FieldError::Invalid {
+                r#type,
+                index,
+                value,
+            } => Self::InvalidField {
+                r#type,
+                index,
+                value,
+            },
+        }
+    }
 }
 
 impl TryFrom<&[u8]> for Notification {
@@ -76,8 +266,8 @@ impl TryFrom<&[u8]> for Notification {
     fn try_from(message: &[u8]) -> Result<Self, Self::Error> {
         let ndata = std::str::from_utf8(message).map_err(Self::Error::InvalidUtf8)?;
         let mut ndata_chars = ndata.chars();
-        let ndata_type = ndata_chars.next().unwrap();
-        let mut ndata_fields = ndata_chars.as_str().split(",");
+        let ndata_type = ndata_chars.next().ok_or(Self::Error::Empty)?;
+        let mut ndata_fields = Fields::new(ndata_type, ndata_chars.as_str());
 
         match ndata_type {
             'A' => Ok(Self::PromptBoilAddition(PromptBoilAddition)),
@@ -87,8 +277,8 @@ impl TryFrom<&[u8]> for Notification {
             'E' => Ok(Self::TemperatureReached(TemperatureReached)),
 
             'X' => {
-                let desired = ndata_fields.next().unwrap().parse().unwrap();
-                let current = ndata_fields.next().unwrap().parse().unwrap();
+                let desired = CentiCelsius::from_celsius(ndata_fields.next_parse()?);
+                let current = CentiCelsius::from_celsius(ndata_fields.next_parse()?);
                 Ok(Self::Temp(Temp {
                     desired,
                     current,
@@ -96,10 +286,10 @@ impl TryFrom<&[u8]> for Notification {
             }
 
             'T' => {
-                let active = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let remaining_minutes = ndata_fields.next().unwrap().parse().unwrap();
-                let total_start_time = ndata_fields.next().unwrap().parse().unwrap();
-                let remaining_seconds = ndata_fields.next().unwrap().parse().unwrap();
+                let active = ndata_fields.next_bool()?;
+                let remaining_minutes = ndata_fields.next_parse()?;
+                let total_start_time = ndata_fields.next_parse()?;
+                let remaining_seconds = ndata_fields.next_parse()?;
                 Ok(Self::Timer(Timer {
                     active,
                     remaining_minutes,
@@ -109,14 +299,14 @@ impl TryFrom<&[u8]> for Notification {
             }
 
             'Y' => {
-                let heat_active = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let pump_active = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let auto_mode_active = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let step_ramp_active = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let interaction_mode_active = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let interaction_code = ndata_fields.next().unwrap().parse().unwrap();
-                let step_number = ndata_fields.next().unwrap().parse().unwrap();
-                let delayed_heat_mode_active = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
+                let heat_active = ndata_fields.next_bool()?;
+                let pump_active = ndata_fields.next_bool()?;
+                let auto_mode_active = ndata_fields.next_bool()?;
+                let step_ramp_active = ndata_fields.next_bool()?;
+                let interaction_mode_active = ndata_fields.next_bool()?;
+                let interaction_code = ndata_fields.next_parse()?;
+                let step_number = ndata_fields.next_parse()?;
+                let delayed_heat_mode_active = ndata_fields.next_bool()?;
                 Ok(Self::Status1(Status1 {
                     heat_active,
                     pump_active,
@@ -130,12 +320,12 @@ impl TryFrom<&[u8]> for Notification {
             }
 
             'W' => {
-                let heat_power_output_percentage = ndata_fields.next().unwrap().parse().unwrap();
-                let timer_paused = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let step_mash_mode = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let recipe_interrupted = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let manual_power_mode = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let sparge_water_alert_displayed = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
+                let heat_power_output_percentage = ndata_fields.next_parse()?;
+                let timer_paused = ndata_fields.next_bool()?;
+                let step_mash_mode = ndata_fields.next_bool()?;
+                let recipe_interrupted = ndata_fields.next_bool()?;
+                let manual_power_mode = ndata_fields.next_bool()?;
+                let sparge_water_alert_displayed = ndata_fields.next_bool()?;
                 Ok(Self::Status2(Status2 {
                     heat_power_output_percentage,
                     timer_paused,
@@ -147,29 +337,29 @@ impl TryFrom<&[u8]> for Notification {
             }
 
             'I' => {
-                let interaction_code = ndata_fields.next().unwrap().parse().unwrap();
+                let interaction_code = ndata_fields.next_parse()?;
                 Ok(Self::Interaction(Interaction {
                     interaction_code,
                 }))
             }
 
             'C' => {
-                let boil_temperature = ndata_fields.next().unwrap().parse().unwrap();
+                let boil_temperature = CentiCelsius::from_celsius(ndata_fields.next_parse()?);
                 Ok(Self::Boil(Boil {
                     boil_temperature,
                 }))
             }
 
             'F' => {
-                let firmware_version = ndata_fields.next().unwrap().to_string();
+                let firmware_version = ndata_fields.next_str()?.to_string();
                 Ok(Self::FirmwareVersion(FirmwareVersion {
                     firmware_version,
                 }))
             }
 
             'V' => {
-                let voltage_is_110 = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
-                let units_are_celsius = ndata_fields.next().unwrap().parse::<u8>().unwrap() == 1;
+                let voltage_is_110 = ndata_fields.next_bool()?;
+                let units_are_celsius = ndata_fields.next_bool()?;
 
                 Ok(Self::VoltageAndUnits(VoltageAndUnits {
                     voltage: if voltage_is_110 {
@@ -187,8 +377,180 @@ impl TryFrom<&[u8]> for Notification {
 
             _ => Ok(Self::Other(Other {
                 r#type: ndata_type,
-                data: ndata_chars.as_str().to_string(),
+                data: ndata_chars.as_str().trim_end_matches(' ').to_string(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn parse(frame: &str) -> Result<Notification, NotificationConvertError> {
+        Notification::try_from(frame.as_bytes())
+    }
+
+    #[test]
+    fn parses_status1() {
+        match parse("Y1,0,1,0,1,2,3,0,") {
+            Ok(Notification::Status1(status)) => {
+                assert!(status.heat_active);
+                assert!(!status.pump_active);
+                assert!(status.auto_mode_active);
+                assert!(status.interaction_mode_active);
+                assert_eq!(InteractionCode::AddGrain, status.interaction_code);
+                assert_eq!(3, status.step_number);
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn encodes_padded_frames() {
+        let notification = Notification::Temp(Temp {
+            desired: CentiCelsius::from_degrees(65),
+            current: CentiCelsius::new(2150),
+        });
+
+        assert_eq!(&b"X65,21.5,        "[..], notification.to_vec().unwrap().as_slice());
+    }
+
+    #[test]
+    fn rejects_oversize_notification() {
+        let notification = Notification::FirmwareVersion(FirmwareVersion {
+            firmware_version: "a-very-long-firmware-version".into(),
+        });
+
+        assert!(matches!(notification.to_vec(), Err(NotificationEncodeError::TooLong(30))));
+    }
+
+    #[test]
+    fn rejects_empty_frame() {
+        assert!(matches!(parse(""), Err(NotificationConvertError::Empty)));
+    }
+
+    #[test]
+    fn rejects_missing_field() {
+        assert!(matches!(
+            parse("X65.0"),
+            Err(NotificationConvertError::MissingField {
+                r#type: 'X',
+                index: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_field() {
+        match parse("T1,6x,61,0,") {
+            Err(NotificationConvertError::InvalidField {
+                r#type: 'T',
+                index: 1,
+                value,
+            }) => assert_eq!("6x", value),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    fn interaction_code() -> impl Strategy<Value = InteractionCode> {
+        prop_oneof![
+            Just(InteractionCode::Dismiss),
+            Just(InteractionCode::None),
+            Just(InteractionCode::SkipDelayedRecipe),
+            Just(InteractionCode::AddGrain),
+            Just(InteractionCode::MashOutDoneStartSparge),
+            Just(InteractionCode::Sparge),
+            Just(InteractionCode::BoilReached),
+            Just(InteractionCode::BoilFinished),
+        ]
+    }
+
+    fn temperature() -> impl Strategy<Value = CentiCelsius> {
+        (0..=12000isize).prop_map(CentiCelsius::new)
+    }
+
+    fn notification() -> impl Strategy<Value = Notification> {
+        prop_oneof![
+            Just(Notification::PromptBoilAddition(PromptBoilAddition)),
+            Just(Notification::PromptSpargeWater(PromptSpargeWater)),
+            Just(Notification::TemperatureReached(TemperatureReached)),
+            (temperature(), temperature()).prop_map(|(desired, current)| Notification::Temp(Temp {
+                desired,
+                current
             })),
+            (any::<bool>(), 0..=999u32, 0..60u32, 0..=999u32).prop_map(
+                |(active, remaining_minutes, remaining_seconds, total_start_time)| Notification::Timer(Timer {
+                    active,
+                    remaining_minutes,
+                    remaining_seconds,
+                    total_start_time,
+                })
+            ),
+            (any::<[bool; 6]>(), interaction_code(), 0..=9u8).prop_map(|(flags, interaction_code, step_number)| {
+                Notification::Status1(Status1 {
+                    heat_active: flags[0],
+                    pump_active: flags[1],
+                    auto_mode_active: flags[2],
+                    step_ramp_active: flags[3],
+                    interaction_mode_active: flags[4],
+                    interaction_code,
+                    step_number,
+                    delayed_heat_mode_active: flags[5],
+                })
+            }),
+            (0..=100u8, any::<[bool; 5]>()).prop_map(|(heat_power_output_percentage, flags)| {
+                Notification::Status2(Status2 {
+                    heat_power_output_percentage,
+                    timer_paused: flags[0],
+                    step_mash_mode: flags[1],
+                    recipe_interrupted: flags[2],
+                    manual_power_mode: flags[3],
+                    sparge_water_alert_displayed: flags[4],
+                })
+            }),
+            interaction_code().prop_map(|interaction_code| Notification::Interaction(Interaction {
+                interaction_code
+            })),
+            temperature().prop_map(|boil_temperature| Notification::Boil(Boil {
+                boil_temperature
+            })),
+            "[0-9.]{1,14}".prop_map(|firmware_version| Notification::FirmwareVersion(FirmwareVersion {
+                firmware_version
+            })),
+            (any::<bool>(), any::<bool>()).prop_map(|(is_110, is_celsius)| {
+                Notification::VoltageAndUnits(VoltageAndUnits {
+                    voltage: if is_110 {
+                        Voltage::V110
+                    } else {
+                        Voltage::V230
+                    },
+                    units: if is_celsius {
+                        Units::Celsius
+                    } else {
+                        Units::Fahrenheit
+                    },
+                })
+            }),
+            ("[GHJ-SU]", "([a-z0-9,]{0,14}[a-z0-9,])?").prop_map(|(r#type, data)| Notification::Other(Other {
+                r#type: r#type.chars().next().unwrap(),
+                data,
+            })),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_notifications(notification in notification()) {
+            let frame = notification.to_vec().unwrap();
+            prop_assert_eq!(NOTIFICATION_LEN, frame.len());
+
+            let decoded = Notification::try_from(frame.as_slice()).unwrap();
+            prop_assert_eq!(&notification, &decoded);
+
+            // Re-encoding the parsed frame yields exactly the same bytes
+            prop_assert_eq!(frame, decoded.to_vec().unwrap());
         }
     }
 }
@@ -1,8 +1,16 @@
-use super::command::{finish_command, COMMAND_LEN};
+use super::command::{finish_command, format_temperature, CommandEncodeError, COMMAND_LEN};
+use crate::calc;
+use bm_units::{celsius, litres, CentiCelsius, Millilitres};
 use std::fmt::Write;
 
+mod decoder;
+pub use decoder::*;
+
+mod validation;
+pub use validation::*;
+
 /// The amount of time to wait before automatically starting a recipe.
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum RecipeDelay {
     None,
@@ -10,27 +18,32 @@ pub enum RecipeDelay {
 }
 
 /// The temperature and duration for a step in the mashing process for a recipe.
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct MashStep {
-    pub temperature: u8,
+    /// The temperature of the step, which is sent to the controller in whole degrees.
+    #[serde(with = "celsius")]
+    pub temperature: CentiCelsius,
     pub minutes: u8,
 }
 
 /// All the information required by the Grainfather controller in its
 /// automatic mode.
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Recipe {
     /// The temperature for the boil
-    pub boil_temperature: f64,
+    #[serde(with = "celsius")]
+    pub boil_temperature: CentiCelsius,
 
     /// The total duration of the boil
     pub boil_time: u8,
 
     /// The volume of water present for the mash
-    pub mash_volume: f64,
+    #[serde(with = "litres")]
+    pub mash_volume: Millilitres,
 
     /// The volume of water added during the sparge
-    pub sparge_volume: f64,
+    #[serde(with = "litres")]
+    pub sparge_volume: Millilitres,
 
     /// Determines whether the controller shows a water treatment
     /// prompt on the countdown to heating the strike water.
@@ -65,9 +78,14 @@ pub struct Recipe {
     #[serde(default)]
     pub boil_power_mode: bool,
 
-    // NOTE: according to kingpulsar, this may not be implemented
-    #[serde(default)]
-    strike_temp_mode: bool,
+    /// The temperature to heat the strike water to before the grain is added, this
+    /// enables the controller's strike temperature mode. Without it the strike water
+    /// is heated to the first mash step's temperature.
+    ///
+    /// See [enable_strike_temperature](crate::Recipe::enable_strike_temperature) to
+    /// calculate this from the grain bill.
+    #[serde(default, with = "celsius::option")]
+    pub strike_temperature: Option<CentiCelsius>,
 
     /// The times (from the end of the boil) at which additions should be added to the boil
     pub boil_steps: Vec<u8>,
@@ -77,7 +95,11 @@ pub struct Recipe {
 }
 
 impl Recipe {
-    pub fn to_commands(&self) -> Vec<Vec<u8>> {
+    /// Encodes the recipe into the sequence of frames used to upload it to the controller, failing
+    /// if any of the recipe's values can't be represented in a frame.
+    ///
+    /// Use [validate](crate::Recipe::validate) to check a recipe against the controller's limits first.
+    pub fn to_commands(&self) -> Result<Vec<Vec<u8>>, CommandEncodeError> {
         // TODO: this can be computed
         let mut commands = Vec::with_capacity(10);
 
@@ -89,12 +111,12 @@ impl Recipe {
                 "R{},{},{:.2},{:.2},",
                 self.boil_time,
                 self.mash_steps.len(),
-                self.mash_volume,
-                self.sparge_volume
+                self.mash_volume.litres(),
+                self.sparge_volume.litres()
             )
             .unwrap();
 
-            finish_command(command)
+            finish_command(command)?
         });
 
         commands.push({
@@ -131,13 +153,13 @@ impl Recipe {
             )
             .unwrap();
 
-            finish_command(command)
+            finish_command(command)?
         });
 
         commands.push({
             let mut command = String::with_capacity(COMMAND_LEN);
             command.push_str(self.name.as_ref());
-            finish_command(command)
+            finish_command(command)?
         });
 
         commands.push({
@@ -153,7 +175,7 @@ impl Recipe {
                 } else {
                     '0'
                 },
-                if self.strike_temp_mode {
+                if self.strike_temperature.is_some() {
                     '1'
                 } else {
                     '0'
@@ -161,22 +183,22 @@ impl Recipe {
             )
             .unwrap();
 
-            finish_command(command)
+            finish_command(command)?
         });
 
         for boil_step in self.boil_steps.iter() {
             commands.push({
                 let mut command = String::with_capacity(COMMAND_LEN);
                 write!(command, "{},", boil_step).unwrap();
-                finish_command(command).into()
+                finish_command(command)?
             })
         }
 
-        if self.strike_temp_mode {
+        if let Some(strike_temperature) = self.strike_temperature {
             commands.push({
                 let mut command = String::with_capacity(COMMAND_LEN);
-                command.push('0');
-                finish_command(command)
+                write!(command, "{},", format_temperature(strike_temperature)).unwrap();
+                finish_command(command)?
             })
         }
 
@@ -187,8 +209,8 @@ impl Recipe {
         {
             commands.push({
                 let mut command = String::with_capacity(COMMAND_LEN);
-                write!(command, "{}:{},", temperature, minutes).unwrap();
-                finish_command(command)
+                write!(command, "{}:{},", temperature.round_to_degrees(), minutes).unwrap();
+                finish_command(command)?
             })
         }
 
@@ -196,21 +218,44 @@ impl Recipe {
             commands.push({
                 let mut command = String::with_capacity(COMMAND_LEN);
                 write!(command, "{},{},", minutes, seconds).unwrap();
-                finish_command(command).into()
+                finish_command(command)?
             })
         }
 
-        commands
+        Ok(commands)
+    }
+
+    /// Enables strike temperature mode, with the strike temperature calculated so that
+    /// the mash settles at the first mash step's temperature once the grain is added.
+    ///
+    /// Returns the strike temperature, or `None` (leaving the recipe unchanged) if the
+    /// recipe has no mash steps or mash water, or the grain bill isn't a positive mass.
+    pub fn enable_strike_temperature(
+        &mut self,
+        grain_bill_kg: f64,
+        grain_temperature: CentiCelsius,
+    ) -> Option<CentiCelsius> {
+        let first_step = self.mash_steps.first()?;
+
+        let strike_temperature = calc::strike_temperature_metric(
+            grain_bill_kg,
+            grain_temperature,
+            self.mash_volume,
+            first_step.temperature,
+        )?;
+
+        self.strike_temperature = Some(strike_temperature);
+        Some(strike_temperature)
     }
 }
 
 impl Default for Recipe {
     fn default() -> Self {
         Self {
-            boil_temperature: 99.5,
+            boil_temperature: CentiCelsius::new(9950),
             boil_time: 60,
-            mash_volume: 13.25,
-            sparge_volume: 14.64,
+            mash_volume: Millilitres::new(13250),
+            sparge_volume: Millilitres::new(14640),
             show_water_treatment_alert: false,
             show_sparge_counter: true,
             show_sparge_alert: true,
@@ -219,7 +264,7 @@ impl Default for Recipe {
             name: String::default(),
             hop_stand_time: 0,
             boil_power_mode: false,
-            strike_temp_mode: false,
+            strike_temperature: None,
             boil_steps: Vec::with_capacity(4),
             mash_steps: Vec::with_capacity(4),
         }
@@ -0,0 +1,362 @@
+//! Reconstruction of recipes from the command frames produced by
+//! [Recipe::to_commands](crate::Recipe::to_commands).
+
+use super::super::command::COMMAND_LEN;
+use super::{MashStep, Recipe, RecipeDelay};
+use bm_units::{CentiCelsius, Millilitres};
+use std::str::FromStr;
+
+/// Identifies a frame within the sequence of frames used to upload a recipe.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RecipeFrame {
+    /// The `R` frame with the boil time, mash step count, and volumes.
+    Header,
+
+    /// The frame with the alert, sparge counter, delay, and skip start flags.
+    Flags,
+
+    /// The frame containing the recipe's name.
+    Name,
+
+    /// The frame with the hop stand time, boil step count, and power/strike modes.
+    BoilHeader,
+
+    /// The boil addition with the given (zero-based) index.
+    BoilStep(u8),
+
+    /// The strike temperature frame, present when strike temperature mode is enabled.
+    StrikeTemperature,
+
+    /// The mash step with the given (zero-based) index.
+    MashStep(u8),
+
+    /// The delayed start frame, present when the recipe has a delay.
+    Delay,
+}
+
+/// Possible errors encountered when decoding a recipe from its frames.
+#[derive(Debug)]
+pub enum RecipeDecodeError {
+    /// The frame was not valid UTF-8.
+    InvalidUtf8(std::str::Utf8Error),
+
+    /// The frame was not the 19 bytes expected of a command.
+    InvalidLength(usize),
+
+    /// The frame couldn't be interpreted as the frame expected next in the sequence, this
+    /// generally means that frames have been lost or have arrived out of order.
+    UnexpectedFrame {
+        expected: RecipeFrame,
+        frame: String,
+    },
+
+    /// The sequence of frames ended before the recipe was complete.
+    Incomplete {
+        expected: RecipeFrame,
+    },
+}
+
+/// Rebuilds a [Recipe](crate::Recipe) from the frames sent to the controller, one frame at a time.
+///
+/// Note that the boil temperature isn't part of a recipe upload, so decoded recipes
+/// always have the default boil temperature.
+///
+/// After an error the decoder discards the partially decoded recipe, and expects the
+/// next frame to be the header of a new recipe.
+#[derive(Clone, Debug)]
+pub struct RecipeDecoder {
+    expected: RecipeFrame,
+    recipe: Recipe,
+    boil_step_count: u8,
+    mash_step_count: u8,
+    strike_temperature_mode: bool,
+}
+
+impl RecipeDecoder {
+    pub fn new() -> Self {
+        Self {
+            expected: RecipeFrame::Header,
+            recipe: Recipe::default(),
+            boil_step_count: 0,
+            mash_step_count: 0,
+            strike_temperature_mode: false,
+        }
+    }
+
+    /// The frame that the decoder expects to receive next.
+    pub fn expected(&self) -> RecipeFrame {
+        self.expected
+    }
+
+    /// Determines whether the decoder is part way through decoding a recipe.
+    pub fn in_progress(&self) -> bool {
+        self.expected != RecipeFrame::Header
+    }
+
+    /// Checks that the decoder isn't part way through a recipe, this should be
+    /// called once the frames have run out.
+    pub fn finish(&self) -> Result<(), RecipeDecodeError> {
+        if self.in_progress() {
+            Err(RecipeDecodeError::Incomplete {
+                expected: self.expected,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Processes the next frame, returning the recipe once its final frame has been processed.
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<Recipe>, RecipeDecodeError> {
+        let result = self.push_prime(frame);
+
+        if result.is_err() {
+            *self = Self::new();
+        }
+
+        result
+    }
+
+    fn push_prime(&mut self, frame: &[u8]) -> Result<Option<Recipe>, RecipeDecodeError> {
+        if frame.len() != COMMAND_LEN {
+            return Err(RecipeDecodeError::InvalidLength(frame.len()));
+        }
+
+        let frame = std::str::from_utf8(frame).map_err(RecipeDecodeError::InvalidUtf8)?.trim_end_matches(' ');
+
+        self.decode_frame(frame).ok_or_else(|| RecipeDecodeError::UnexpectedFrame {
+            expected: self.expected,
+            frame: frame.to_string(),
+        })?;
+
+        if self.expected == RecipeFrame::Header {
+            let recipe = std::mem::take(&mut self.recipe);
+            *self = Self::new();
+            Ok(Some(recipe))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Decodes the expected frame into the recipe, and moves on to the next expected frame.
+    fn decode_frame(&mut self, frame: &str) -> Option<()> {
+        let recipe = &mut self.recipe;
+
+        match self.expected {
+            RecipeFrame::Header => {
+                let mut fields = frame.strip_prefix('R')?.split(',');
+                recipe.boil_time = field(&mut fields)?;
+                self.mash_step_count = field(&mut fields)?;
+                recipe.mash_volume = Millilitres::from_litres(field(&mut fields)?);
+                recipe.sparge_volume = Millilitres::from_litres(field(&mut fields)?);
+                self.expected = RecipeFrame::Flags;
+            }
+
+            RecipeFrame::Flags => {
+                let mut fields = frame.split(',');
+                recipe.show_water_treatment_alert = flag(&mut fields)?;
+                recipe.show_sparge_counter = flag(&mut fields)?;
+                recipe.show_sparge_alert = flag(&mut fields)?;
+                recipe.delay = if flag(&mut fields)? {
+                    RecipeDelay::MinutesSeconds(0, 0)
+                } else {
+                    RecipeDelay::None
+                };
+                recipe.skip_start = flag(&mut fields)?;
+                self.expected = RecipeFrame::Name;
+            }
+
+            RecipeFrame::Name => {
+                recipe.name = frame.to_string();
+                self.expected = RecipeFrame::BoilHeader;
+            }
+
+            RecipeFrame::BoilHeader => {
+                let mut fields = frame.split(',');
+                recipe.hop_stand_time = field(&mut fields)?;
+                self.boil_step_count = field(&mut fields)?;
+                recipe.boil_power_mode = flag(&mut fields)?;
+                self.strike_temperature_mode = flag(&mut fields)?;
+                self.expected = self.after_boil_steps(0);
+            }
+
+            RecipeFrame::BoilStep(index) => {
+                let mut fields = frame.split(',');
+                recipe.boil_steps.push(field(&mut fields)?);
+                self.expected = self.after_boil_steps(index + 1);
+            }
+
+            RecipeFrame::StrikeTemperature => {
+                recipe.strike_temperature = Some(CentiCelsius::from_celsius(field(&mut frame.split(','))?));
+                self.expected = self.after_mash_steps(0);
+            }
+
+            RecipeFrame::MashStep(index) => {
+                let mut fields = frame.strip_suffix(',')?.split(':');
+                let temperature = CentiCelsius::from_degrees(field(&mut fields)?);
+                let minutes = field(&mut fields)?;
+                recipe.mash_steps.push(MashStep {
+                    temperature,
+                    minutes,
+                });
+                self.expected = self.after_mash_steps(index + 1);
+            }
+
+            RecipeFrame::Delay => {
+                let mut fields = frame.split(',');
+                let minutes = field(&mut fields)?;
+                let seconds = field(&mut fields)?;
+                recipe.delay = RecipeDelay::MinutesSeconds(minutes, seconds);
+                self.expected = RecipeFrame::Header;
+            }
+        }
+
+        Some(())
+    }
+
+    fn after_boil_steps(&self, decoded: u8) -> RecipeFrame {
+        if decoded < self.boil_step_count {
+            RecipeFrame::BoilStep(decoded)
+        } else if self.strike_temperature_mode {
+            RecipeFrame::StrikeTemperature
+        } else {
+            self.after_mash_steps(0)
+        }
+    }
+
+    fn after_mash_steps(&self, decoded: u8) -> RecipeFrame {
+        if decoded < self.mash_step_count {
+            RecipeFrame::MashStep(decoded)
+        } else if let RecipeDelay::MinutesSeconds(_, _) = self.recipe.delay {
+            RecipeFrame::Delay
+        } else {
+            RecipeFrame::Header
+        }
+    }
+}
+
+impl Default for RecipeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Recipe {
+    /// Rebuilds a recipe from exactly the frames produced by [to_commands](crate::Recipe::to_commands).
+    pub fn from_commands<T>(frames: &[T]) -> Result<Self, RecipeDecodeError>
+    where
+        T: AsRef<[u8]>,
+    {
+        let mut decoder = RecipeDecoder::new();
+        let mut frames = frames.iter();
+
+        for frame in &mut frames {
+            if let Some(recipe) = decoder.push(frame.as_ref())? {
+                return match frames.next() {
+                    None => Ok(recipe),
+
+                    Some(extra) => Err(RecipeDecodeError::UnexpectedFrame {
+                        expected: RecipeFrame::Header,
+                        frame: String::from_utf8_lossy(extra.as_ref()).trim_end_matches(' ').to_string(),
+                    }),
+                };
+            }
+        }
+
+        Err(RecipeDecodeError::Incomplete {
+            expected: decoder.expected(),
+        })
+    }
+}
+
+fn field<'a, T>(fields: &mut impl Iterator<Item = &'a str>) -> Option<T>
+where
+    T: FromStr,
+{
+    fields.next()?.parse().ok()
+}
+
+fn flag<'a>(fields: &mut impl Iterator<Item = &'a str>) -> Option<bool> {
+    match fields.next()? {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example_recipe() -> Recipe {
+        Recipe {
+            name: "STIPA".into(),
+            boil_time: 60,
+            delay: RecipeDelay::MinutesSeconds(5, 30),
+            hop_stand_time: 10,
+            boil_power_mode: true,
+            boil_steps: vec![60, 15, 5],
+            mash_steps: vec![
+                MashStep {
+                    temperature: CentiCelsius::from_degrees(65),
+                    minutes: 60,
+                },
+                MashStep {
+                    temperature: CentiCelsius::from_degrees(75),
+                    minutes: 10,
+                },
+            ],
+            ..Recipe::default()
+        }
+    }
+
+    #[test]
+    fn round_trips_recipes() {
+        let recipe = example_recipe();
+        assert_eq!(recipe, Recipe::from_commands(&recipe.to_commands().unwrap()).unwrap());
+
+        let recipe = Recipe {
+            delay: RecipeDelay::None,
+            strike_temperature: Some(CentiCelsius::new(7250)),
+            boil_steps: vec![],
+            ..example_recipe()
+        };
+        assert_eq!(recipe, Recipe::from_commands(&recipe.to_commands().unwrap()).unwrap());
+    }
+
+    #[test]
+    fn decodes_consecutive_recipes() {
+        let commands = example_recipe().to_commands().unwrap();
+        let mut decoder = RecipeDecoder::new();
+
+        for _ in 0..2 {
+            let recipes = commands.iter().filter_map(|frame| decoder.push(frame).unwrap()).collect::<Vec<_>>();
+            assert_eq!(vec![example_recipe()], recipes);
+            decoder.finish().unwrap();
+        }
+    }
+
+    #[test]
+    fn reports_missing_frames() {
+        let mut commands = example_recipe().to_commands().unwrap();
+
+        // Drop the second mash step, so the delay frame arrives in its place
+        commands.remove(commands.len() - 2);
+
+        match Recipe::from_commands(&commands) {
+            Err(RecipeDecodeError::UnexpectedFrame {
+                expected: RecipeFrame::MashStep(1),
+                frame,
+            }) => assert_eq!("5,30,", frame),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        commands.truncate(3);
+
+        assert!(matches!(
+            Recipe::from_commands(&commands),
+            Err(RecipeDecodeError::Incomplete {
+                expected: RecipeFrame::BoilHeader
+            })
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+//! Checks of recipes against the limits of the controller and its recipe frames.
+
+use super::super::command::COMMAND_LEN;
+use super::{MashStep, Recipe, RecipeDelay};
+use bm_units::{CentiCelsius, Millilitres};
+
+/// The maximum number of mash steps, beyond this the recipe header frame can overflow.
+pub const MAX_MASH_STEPS: usize = 9;
+
+/// The maximum number of boil additions.
+pub const MAX_BOIL_STEPS: usize = 9;
+
+/// The range of plausible mash step temperatures.
+pub const MASH_TEMPERATURE_RANGE: (CentiCelsius, CentiCelsius) =
+    (CentiCelsius::from_degrees(20), CentiCelsius::from_degrees(100));
+
+/// The range of plausible boil temperatures.
+pub const BOIL_TEMPERATURE_RANGE: (CentiCelsius, CentiCelsius) =
+    (CentiCelsius::from_degrees(80), CentiCelsius::from_degrees(105));
+
+/// The range of plausible mash and sparge water volumes, the upper limit is the
+/// largest volume that fits in the recipe header frame.
+pub const VOLUME_RANGE: (Millilitres, Millilitres) = (Millilitres::new(0), Millilitres::new(99_990));
+
+/// The longest delay that can be used before starting a recipe, in minutes.
+pub const MAX_DELAY_MINUTES: u16 = 24 * 60;
+
+/// A reason that a recipe can't be sent to the controller.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum RecipeProblem {
+    /// The name is longer than a frame allows.
+    NameTooLong {
+        length: usize,
+        max: usize,
+    },
+
+    /// The name contains a character that the controller can't display, or that
+    /// would corrupt the recipe frames (i.e. a comma).
+    NameInvalidCharacter {
+        character: char,
+    },
+
+    NoMashSteps,
+
+    TooManyMashSteps {
+        count: usize,
+        max: usize,
+    },
+
+    TooManyBoilSteps {
+        count: usize,
+        max: usize,
+    },
+
+    /// The boil addition at the given index happens before the boil starts.
+    BoilAdditionOutsideBoil {
+        index: usize,
+        minutes: u8,
+        boil_time: u8,
+    },
+
+    MashTemperatureOutOfRange {
+        index: usize,
+        temperature: CentiCelsius,
+        min: CentiCelsius,
+        max: CentiCelsius,
+    },
+
+    StrikeTemperatureOutOfRange {
+        temperature: CentiCelsius,
+        min: CentiCelsius,
+        max: CentiCelsius,
+    },
+
+    BoilTemperatureOutOfRange {
+        temperature: CentiCelsius,
+        min: CentiCelsius,
+        max: CentiCelsius,
+    },
+
+    MashVolumeOutOfRange {
+        volume: Millilitres,
+        min: Millilitres,
+        max: Millilitres,
+    },
+
+    SpargeVolumeOutOfRange {
+        volume: Millilitres,
+        min: Millilitres,
+        max: Millilitres,
+    },
+
+    DelayOutOfRange {
+        minutes: u16,
+        seconds: u8,
+        max_minutes: u16,
+    },
+}
+
+impl Recipe {
+    /// Checks the recipe against the limits of the controller, returning every problem
+    /// found. A recipe with no problems can be safely sent to the controller.
+    pub fn validate(&self) -> Vec<RecipeProblem> {
+        let mut problems = Vec::new();
+
+        let name_length = self.name.chars().count();
+
+        if name_length > COMMAND_LEN {
+            problems.push(RecipeProblem::NameTooLong {
+                length: name_length,
+                max: COMMAND_LEN,
+            });
+        }
+
+        if let Some(character) = self.name.chars().find(|c| !c.is_ascii() || c.is_ascii_control() || *c == ',') {
+            problems.push(RecipeProblem::NameInvalidCharacter {
+                character,
+            });
+        }
+
+        if self.mash_steps.is_empty() {
+            problems.push(RecipeProblem::NoMashSteps);
+        }
+
+        if self.mash_steps.len() > MAX_MASH_STEPS {
+            problems.push(RecipeProblem::TooManyMashSteps {
+                count: self.mash_steps.len(),
+                max: MAX_MASH_STEPS,
+            });
+        }
+
+        if self.boil_steps.len() > MAX_BOIL_STEPS {
+            problems.push(RecipeProblem::TooManyBoilSteps {
+                count: self.boil_steps.len(),
+                max: MAX_BOIL_STEPS,
+            });
+        }
+
+        for (index, minutes) in self.boil_steps.iter().enumerate() {
+            if *minutes > self.boil_time {
+                problems.push(RecipeProblem::BoilAdditionOutsideBoil {
+                    index,
+                    minutes: *minutes,
+                    boil_time: self.boil_time,
+                });
+            }
+        }
+
+        let (min, max) = MASH_TEMPERATURE_RANGE;
+
+        for (
+            index,
+            MashStep {
+                temperature,
+                ..
+            },
+        ) in self.mash_steps.iter().enumerate()
+        {
+            if !in_range(*temperature, MASH_TEMPERATURE_RANGE) {
+                problems.push(RecipeProblem::MashTemperatureOutOfRange {
+                    index,
+                    temperature: *temperature,
+                    min,
+                    max,
+                });
+            }
+        }
+
+        if let Some(temperature) = self.strike_temperature {
+            if !in_range(temperature, MASH_TEMPERATURE_RANGE) {
+                problems.push(RecipeProblem::StrikeTemperatureOutOfRange {
+                    temperature,
+                    min,
+                    max,
+                });
+            }
+        }
+
+        let (min, max) = BOIL_TEMPERATURE_RANGE;
+
+        if !in_range(self.boil_temperature, BOIL_TEMPERATURE_RANGE) {
+            problems.push(RecipeProblem::BoilTemperatureOutOfRange {
+                temperature: self.boil_temperature,
+                min,
+                max,
+            });
+        }
+
+        let (min, max) = VOLUME_RANGE;
+
+        if !in_range(self.mash_volume, VOLUME_RANGE) {
+            problems.push(RecipeProblem::MashVolumeOutOfRange {
+                volume: self.mash_volume,
+                min,
+                max,
+            });
+        }
+
+        if !in_range(self.sparge_volume, VOLUME_RANGE) {
+            problems.push(RecipeProblem::SpargeVolumeOutOfRange {
+                volume: self.sparge_volume,
+                min,
+                max,
+            });
+        }
+
+        if let RecipeDelay::MinutesSeconds(minutes, seconds) = self.delay {
+            if minutes > MAX_DELAY_MINUTES || seconds > 59 || (minutes == MAX_DELAY_MINUTES && seconds > 0) {
+                problems.push(RecipeProblem::DelayOutOfRange {
+                    minutes,
+                    seconds,
+                    max_minutes: MAX_DELAY_MINUTES,
+                });
+            }
+        }
+
+        problems
+    }
+}
+
+fn in_range<T>(value: T, (min, max): (T, T)) -> bool
+where
+    T: PartialOrd,
+{
+    value >= min && value <= max
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_recipe() -> Recipe {
+        Recipe {
+            name: "Dry Stout".into(),
+            boil_steps: vec![60, 10],
+            mash_steps: vec![MashStep {
+                temperature: CentiCelsius::from_degrees(67),
+                minutes: 60,
+            }],
+            ..Recipe::default()
+        }
+    }
+
+    #[test]
+    fn accepts_valid_recipe() {
+        assert_eq!(Vec::<RecipeProblem>::new(), valid_recipe().validate());
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let recipe = Recipe {
+            name: "A name that is much, much too long".into(),
+            boil_time: 30,
+            mash_volume: Millilitres::from_litres(120.0),
+            delay: RecipeDelay::MinutesSeconds(10, 60),
+            mash_steps: vec![MashStep {
+                temperature: CentiCelsius::from_degrees(120),
+                minutes: 60,
+            }],
+            ..valid_recipe()
+        };
+
+        assert_eq!(
+            vec![
+                RecipeProblem::NameTooLong {
+                    length: 34,
+                    max: 19
+                },
+                RecipeProblem::NameInvalidCharacter {
+                    character: ','
+                },
+                RecipeProblem::BoilAdditionOutsideBoil {
+                    index: 0,
+                    minutes: 60,
+                    boil_time: 30
+                },
+                RecipeProblem::MashTemperatureOutOfRange {
+                    index: 0,
+                    temperature: CentiCelsius::from_degrees(120),
+                    min: CentiCelsius::from_degrees(20),
+                    max: CentiCelsius::from_degrees(100)
+                },
+                RecipeProblem::MashVolumeOutOfRange {
+                    volume: Millilitres::new(120_000),
+                    min: Millilitres::new(0),
+                    max: Millilitres::new(99_990)
+                },
+                RecipeProblem::DelayOutOfRange {
+                    minutes: 10,
+                    seconds: 60,
+                    max_minutes: MAX_DELAY_MINUTES
+                },
+            ],
+            recipe.validate()
+        );
+    }
+
+    #[test]
+    fn checks_strike_temperature() {
+        let mut recipe = valid_recipe();
+
+        assert_eq!(
+            Some(CentiCelsius::new(7282)),
+            recipe.enable_strike_temperature(4.0, CentiCelsius::from_degrees(20))
+        );
+        assert_eq!(Vec::<RecipeProblem>::new(), recipe.validate());
+
+        recipe.enable_strike_temperature(20.0, CentiCelsius::from_degrees(5));
+        assert!(matches!(recipe.validate().as_slice(), [RecipeProblem::StrikeTemperatureOutOfRange { .. }]));
+
+        // A grain bill that isn't a positive mass leaves the recipe unchanged
+        let mut recipe = valid_recipe();
+
+        assert_eq!(None, recipe.enable_strike_temperature(-4.0, CentiCelsius::from_degrees(20)));
+        assert_eq!(None, recipe.strike_temperature);
+    }
+
+    #[test]
+    fn rejects_negative_volumes() {
+        let recipe = Recipe {
+            sparge_volume: Millilitres::from_litres(-1.0),
+            ..valid_recipe()
+        };
+
+        assert!(matches!(recipe.validate().as_slice(), [RecipeProblem::SpargeVolumeOutOfRange { .. }]));
+    }
+}
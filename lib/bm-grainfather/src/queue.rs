@@ -0,0 +1,332 @@
+use crate::transport::{Transport, TransportError};
+use crate::{Command, DisconnectOption, Error};
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+/// The order in which queued commands are written to the controller.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Priority {
+    Normal,
+
+    /// Written ahead of any queued normal priority commands, though never in the
+    /// middle of a recipe that's already being written.
+    Urgent,
+}
+
+impl Priority {
+    /// The priority of a command, commands which make the controller safe, i.e. turn
+    /// off the heat or pump, or cancel the session, are urgent.
+    pub fn of(command: &Command) -> Self {
+        match command {
+            Command::SetHeatActive(false)
+            | Command::SetPumpActive(false)
+            | Command::CancelOrFinishSession
+            | Command::Disconnect(DisconnectOption::CancelSession)
+            | Command::Disconnect(DisconnectOption::ManualMode) => Self::Urgent,
+
+            _ => Self::Normal,
+        }
+    }
+}
+
+/// Describes the traffic passing through a client's command queue.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct QueueStats {
+    /// The number of commands and recipes waiting to be written.
+    pub depth: usize,
+
+    /// The number of frames written to the controller.
+    pub frames_written: u64,
+
+    /// How long the most recently written command waited in the queue.
+    pub last_latency: Duration,
+
+    /// The longest that any command has waited in the queue.
+    pub max_latency: Duration,
+}
+
+pub(crate) type FrameCallback = Box<dyn FnMut(usize) + Send>;
+
+type Completion = Box<dyn FnOnce(Result<(), TransportError>) + Send>;
+
+/// A command, or recipe, whose frames are written together.
+struct Job {
+    frames: Vec<Vec<u8>>,
+    interval: Duration,
+    on_frame: Option<FrameCallback>,
+    done: Completion,
+    enqueued: Instant,
+}
+
+struct QueueState {
+    urgent: VecDeque<Job>,
+    normal: VecDeque<Job>,
+    min_interval: Duration,
+    closed: bool,
+    stats: QueueStats,
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    available: Condvar,
+}
+
+/// Serializes the frames written to a controller through a single writer thread,
+/// leaving a minimum gap between frames so that the controller isn't overwhelmed.
+pub(crate) struct CommandQueue {
+    shared: Arc<Shared>,
+}
+
+impl CommandQueue {
+    /// The most normal priority commands that may be waiting, beyond this new commands
+    /// are rejected rather than letting the queue grow without bound.
+    pub const MAX_DEPTH: usize = 32;
+
+    pub fn new(transport: Arc<dyn Transport>, min_interval: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState {
+                urgent: VecDeque::new(),
+                normal: VecDeque::new(),
+                min_interval,
+                closed: false,
+                stats: QueueStats::default(),
+            }),
+            available: Condvar::new(),
+        });
+
+        {
+            let shared = shared.clone();
+            std::thread::spawn(move || Self::run(&shared, transport.as_ref()));
+        }
+
+        Self {
+            shared,
+        }
+    }
+
+    pub fn min_interval(&self) -> Duration {
+        self.shared.state.lock().unwrap().min_interval
+    }
+
+    pub fn set_min_interval(&self, min_interval: Duration) {
+        self.shared.state.lock().unwrap().min_interval = min_interval;
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let state = self.shared.state.lock().unwrap();
+
+        QueueStats {
+            depth: state.urgent.len() + state.normal.len(),
+            ..state.stats.clone()
+        }
+    }
+
+    /// Writes frames, blocking until they've been written.
+    pub fn write(&self, priority: Priority, frames: Vec<Vec<u8>>) -> Result<(), Error> {
+        let (sender, receiver) = mpsc::channel();
+
+        self.submit(
+            priority,
+            frames,
+            Duration::from_secs(0),
+            None,
+            Box::new(move |result| {
+                let _ = sender.send(result);
+            }),
+        )?;
+
+        // The completion is only dropped without being called once the queue has closed
+        Ok(receiver.recv().unwrap_or(Err(TransportError::NotConnected))?)
+    }
+
+    /// Queues frames to be written at least the given interval apart, returning a
+    /// receiver for the result, or failing if the queue is full. The callback is called with the index
+    /// of each frame once it has been written.
+    pub fn write_async(
+        &self,
+        priority: Priority,
+        frames: Vec<Vec<u8>>,
+        interval: Duration,
+        on_frame: Option<FrameCallback>,
+    ) -> Result<oneshot::Receiver<Result<(), TransportError>>, Error> {
+        let (sender, receiver) = oneshot::channel();
+
+        self.submit(
+            priority,
+            frames,
+            interval,
+            on_frame,
+            Box::new(move |result| {
+                let _ = sender.send(result);
+            }),
+        )?;
+
+        Ok(receiver)
+    }
+
+    fn submit(
+        &self,
+        priority: Priority,
+        frames: Vec<Vec<u8>>,
+        interval: Duration,
+        on_frame: Option<FrameCallback>,
+        done: Completion,
+    ) -> Result<(), Error> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        let job = Job {
+            frames,
+            interval,
+            on_frame,
+            done,
+            enqueued: Instant::now(),
+        };
+
+        match priority {
+            Priority::Urgent => state.urgent.push_back(job),
+
+            Priority::Normal if state.normal.len() < Self::MAX_DEPTH => state.normal.push_back(job),
+
+            Priority::Normal => return Err(Error::QueueFull(state.normal.len())),
+        }
+
+        self.shared.available.notify_one();
+        Ok(())
+    }
+
+    fn run(shared: &Shared, transport: &dyn Transport) {
+        let mut last_write = None;
+
+        loop {
+            let (job, min_interval) = {
+                let mut state = shared.state.lock().unwrap();
+
+                loop {
+                    // Any jobs still queued are dropped, which reports them as not connected
+                    if state.closed {
+                        return;
+                    }
+
+                    if let Some(job) = state.urgent.pop_front().or_else(|| state.normal.pop_front()) {
+                        let latency = job.enqueued.elapsed();
+                        state.stats.last_latency = latency;
+                        state.stats.max_latency = state.stats.max_latency.max(latency);
+
+                        break (job, state.min_interval);
+                    }
+
+                    state = shared.available.wait(state).unwrap();
+                }
+            };
+
+            let Job {
+                frames,
+                interval,
+                mut on_frame,
+                done,
+                ..
+            } = job;
+
+            let mut result = Ok(());
+
+            for (index, frame) in frames.iter().enumerate() {
+                let gap = if index == 0 {
+                    min_interval
+                } else {
+                    interval.max(min_interval)
+                };
+
+                if let Some(wait) =
+                    last_write.and_then(|last_write: Instant| (last_write + gap).checked_duration_since(Instant::now()))
+                {
+                    std::thread::sleep(wait);
+                }
+
+                result = transport.write(frame);
+                last_write = Some(Instant::now());
+
+                if result.is_err() {
+                    break;
+                }
+
+                shared.state.lock().unwrap().stats.frames_written += 1;
+
+                if let Some(on_frame) = on_frame.as_mut() {
+                    on_frame(index);
+                }
+            }
+
+            drop(on_frame);
+            done(result);
+        }
+    }
+}
+
+impl Drop for CommandQueue {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.available.notify_all();
+    }
+}
+
+impl std::fmt::Debug for CommandQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandQueue").field("stats", &self.stats()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::LoopbackTransport;
+
+    #[tokio::test]
+    async fn writes_urgent_commands_first() {
+        let (transport, controller) = LoopbackTransport::new();
+        let queue = CommandQueue::new(Arc::new(transport), Duration::from_millis(20));
+        let (started_sender, started) = mpsc::channel();
+
+        let recipe = queue
+            .write_async(
+                Priority::Normal,
+                vec![b"R1".to_vec(), b"R2".to_vec()],
+                Duration::from_secs(0),
+                Some(Box::new(move |index| {
+                    let _ = started_sender.send(index);
+                })),
+            )
+            .unwrap();
+
+        // Once the recipe has started it's written as a whole, but the urgent
+        // command overtakes the one queued behind it
+        assert_eq!(0, started.recv().unwrap());
+
+        let normal = queue.write_async(Priority::Normal, vec![b"N".to_vec()], Duration::from_secs(0), None).unwrap();
+        assert_eq!(1, queue.stats().depth);
+
+        queue.write(Priority::Urgent, vec![b"U".to_vec()]).unwrap();
+        recipe.await.unwrap().unwrap();
+        normal.await.unwrap().unwrap();
+
+        assert_eq!(vec![b"R1".to_vec(), b"R2".to_vec(), b"U".to_vec(), b"N".to_vec()], controller.take_written());
+        assert_eq!(4, queue.stats().frames_written);
+        assert!(queue.stats().max_latency >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn rejects_commands_once_full() {
+        let (transport, _controller) = LoopbackTransport::new();
+        let queue = CommandQueue::new(Arc::new(transport), Duration::from_secs(3600));
+
+        // The writer waits between frames for longer than the test runs, so the queue fills
+        let mut results = (0..CommandQueue::MAX_DEPTH + 3)
+            .map(|_| queue.write_async(Priority::Normal, vec![b"N".to_vec()], Duration::from_secs(0), None));
+
+        let error = results.find_map(Result::err);
+
+        assert!(matches!(error, Some(Error::QueueFull(CommandQueue::MAX_DEPTH))));
+        assert!(queue.write_async(Priority::Urgent, vec![b"U".to_vec()], Duration::from_secs(0), None).is_ok());
+    }
+}
@@ -0,0 +1,367 @@
+//! Tracks the progress of a recipe through the phases of a brew day.
+
+use crate::notifications::*;
+use crate::{InteractionCode, Notification, Recipe, RecipeDelay, StepNumber};
+use bm_units::CentiCelsius;
+use std::time::Duration;
+
+/// The phase of a brew day that the controller is in, see [StepNumber](crate::StepNumber)
+/// for how these relate to the controller's step numbers.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum BrewPhase {
+    /// The recipe is waiting for its delayed start.
+    DelayedStart,
+
+    /// The strike water is heating to the first mash step's temperature.
+    HeatingStrike,
+
+    /// The strike water is at temperature, and the controller is waiting for the grain.
+    AddGrain,
+
+    /// The given mash step (one-based) is in progress, this includes the
+    /// ramp up to its temperature.
+    MashStep(StepNumber),
+
+    /// The final mash step of a multi-step mash is in progress, and it's hot enough
+    /// to be a mash out.
+    MashOut,
+
+    Sparge,
+    HeatingToBoil,
+    Boil,
+
+    /// The boil is done and the hop stand timer is running.
+    HopStand,
+
+    Finished,
+}
+
+/// Combines a recipe loaded into the controller with the notifications it emits
+/// to determine where in the recipe the brew day is.
+#[derive(Clone, Debug)]
+pub struct BrewSession {
+    recipe: Recipe,
+    status: Status1,
+    timer: Timer,
+    interaction: Option<InteractionCode>,
+}
+
+impl BrewSession {
+    /// The lowest temperature at which the final step of a multi-step mash is considered
+    /// to be a mash out, rather than another rest.
+    pub const MASH_OUT_TEMPERATURE: CentiCelsius = CentiCelsius::from_degrees(75);
+
+    pub fn new(recipe: Recipe) -> Self {
+        Self {
+            recipe,
+            status: Status1 {
+                heat_active: false,
+                pump_active: false,
+                auto_mode_active: true,
+                step_ramp_active: false,
+                interaction_mode_active: false,
+                interaction_code: InteractionCode::None,
+                step_number: 0,
+                delayed_heat_mode_active: false,
+            },
+            timer: Timer {
+                active: false,
+                remaining_minutes: 0,
+                remaining_seconds: 0,
+                total_start_time: 0,
+            },
+            interaction: None,
+        }
+    }
+
+    pub fn recipe(&self) -> &Recipe {
+        &self.recipe
+    }
+
+    /// The interaction the controller is waiting on the user for, if any.
+    pub fn interaction(&self) -> Option<&InteractionCode> {
+        self.interaction.as_ref()
+    }
+
+    /// Updates the session from a notification emitted by the controller, notifications
+    /// which aren't relevant to the session are ignored.
+    pub fn handle_notification(&mut self, notification: &Notification) {
+        match notification {
+            Notification::Status1(status) => {
+                self.interaction = if status.interaction_mode_active {
+                    Some(status.interaction_code.clone())
+                } else {
+                    None
+                };
+
+                self.status = status.clone();
+            }
+
+            Notification::Timer(timer) => self.timer = timer.clone(),
+
+            Notification::Interaction(Interaction {
+                interaction_code,
+            }) => {
+                self.interaction = match interaction_code {
+                    InteractionCode::Dismiss | InteractionCode::None => None,
+                    other => Some(other.clone()),
+                };
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Determines whether the controller has left the recipe, i.e. the session
+    /// has been finished or cancelled.
+    pub fn is_active(&self) -> bool {
+        self.status.auto_mode_active
+    }
+
+    pub fn phase(&self) -> BrewPhase {
+        let mash_step_count = self.recipe.mash_steps.len() as StepNumber;
+        let step_number = self.status.step_number;
+
+        if !self.is_active() {
+            return BrewPhase::Finished;
+        }
+
+        // Recipes without a delay only wait at step zero until the controller starts them
+        if step_number == 0 {
+            return match self.recipe.delay {
+                RecipeDelay::MinutesSeconds(_, _) => BrewPhase::DelayedStart,
+                RecipeDelay::None => BrewPhase::HeatingStrike,
+            };
+        }
+
+        if step_number <= mash_step_count {
+            if self.interaction == Some(InteractionCode::AddGrain) {
+                return BrewPhase::AddGrain;
+            }
+
+            if step_number == 1 && self.is_ramping() {
+                return BrewPhase::HeatingStrike;
+            }
+
+            if step_number == mash_step_count && mash_step_count > 1 && self.is_mash_out(step_number) {
+                return BrewPhase::MashOut;
+            }
+
+            return BrewPhase::MashStep(step_number);
+        }
+
+        match step_number - mash_step_count {
+            1 => BrewPhase::Sparge,
+            2 if self.is_ramping() => BrewPhase::HeatingToBoil,
+            2 => BrewPhase::Boil,
+            3 if self.recipe.hop_stand_time > 0 && self.timer.remaining_minutes > 0 => BrewPhase::HopStand,
+            _ => BrewPhase::Finished,
+        }
+    }
+
+    /// The time remaining in the current phase, if it's timed. Phases which
+    /// wait on temperature or the user have no time remaining.
+    pub fn time_remaining(&self) -> Option<Duration> {
+        match self.phase() {
+            BrewPhase::DelayedStart
+            | BrewPhase::MashStep(_)
+            | BrewPhase::MashOut
+            | BrewPhase::Boil
+            | BrewPhase::HopStand => self.timer_remaining(),
+
+            _ => None,
+        }
+    }
+
+    /// The boil addition (in minutes before the end of the boil) that is currently
+    /// due, i.e. the most recent addition whose time has been reached.
+    pub fn active_boil_addition(&self) -> Option<u8> {
+        if self.phase() != BrewPhase::Boil {
+            return None;
+        }
+
+        let remaining = self.timer_remaining()?;
+
+        self.recipe
+            .boil_steps
+            .iter()
+            .copied()
+            .filter(|minutes| Duration::from_secs(u64::from(*minutes) * 60) >= remaining)
+            .min()
+    }
+
+    /// The controller reports when it's ramping up to a step's temperature, its timers
+    /// also stop while the user has paused them, so they can't be relied on for this.
+    fn is_ramping(&self) -> bool {
+        self.status.step_ramp_active
+    }
+
+    /// Determines whether the given mash step (one-based) is hot enough to be a mash out.
+    fn is_mash_out(&self, step_number: StepNumber) -> bool {
+        self.recipe
+            .mash_steps
+            .get(usize::from(step_number) - 1)
+            .map(|step| step.temperature >= Self::MASH_OUT_TEMPERATURE)
+            .unwrap_or(false)
+    }
+
+    /// The controller reports one more minute than remains, with the final minute
+    /// reported as a seconds countdown. A timer reporting zero minutes isn't running.
+    fn timer_remaining(&self) -> Option<Duration> {
+        match self.timer.remaining_minutes {
+            0 => None,
+            minutes => Some(Duration::from_secs(u64::from(minutes - 1) * 60 + u64::from(self.timer.remaining_seconds))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MashStep;
+
+    fn recipe() -> Recipe {
+        let mut recipe = Recipe::default();
+        recipe.delay = RecipeDelay::MinutesSeconds(30, 0);
+        recipe.boil_time = 60;
+        recipe.hop_stand_time = 20;
+        recipe.boil_steps = vec![60, 15, 5];
+        recipe.mash_steps = vec![
+            MashStep {
+                temperature: CentiCelsius::from_degrees(65),
+                minutes: 60,
+            },
+            MashStep {
+                temperature: CentiCelsius::from_degrees(75),
+                minutes: 10,
+            },
+        ];
+        recipe
+    }
+
+    fn update(session: &mut BrewSession, step_number: StepNumber, timer_minutes: u32, interaction: InteractionCode) {
+        session.handle_notification(&Notification::Status1(Status1 {
+            heat_active: true,
+            pump_active: true,
+            auto_mode_active: true,
+            step_ramp_active: timer_minutes == 0,
+            interaction_mode_active: interaction != InteractionCode::None,
+            interaction_code: interaction,
+            step_number,
+            delayed_heat_mode_active: false,
+        }));
+
+        session.handle_notification(&Notification::Timer(Timer {
+            active: timer_minutes > 0,
+            remaining_minutes: timer_minutes,
+            remaining_seconds: 0,
+            total_start_time: 61,
+        }));
+    }
+
+    #[test]
+    fn follows_the_phases_of_a_recipe() {
+        let mut session = BrewSession::new(recipe());
+        let mut phases = Vec::new();
+
+        for (step_number, timer_minutes, interaction) in vec![
+            (0, 30, InteractionCode::None),
+            (1, 0, InteractionCode::None),
+            (1, 0, InteractionCode::AddGrain),
+            (1, 45, InteractionCode::None),
+            (2, 0, InteractionCode::None),
+            (3, 0, InteractionCode::Sparge),
+            (4, 0, InteractionCode::None),
+            (4, 30, InteractionCode::None),
+            (5, 10, InteractionCode::None),
+            (5, 0, InteractionCode::BoilFinished),
+        ] {
+            update(&mut session, step_number, timer_minutes, interaction);
+            phases.push(session.phase());
+        }
+
+        assert_eq!(
+            vec![
+                BrewPhase::DelayedStart,
+                BrewPhase::HeatingStrike,
+                BrewPhase::AddGrain,
+                BrewPhase::MashStep(1),
+                BrewPhase::MashOut,
+                BrewPhase::Sparge,
+                BrewPhase::HeatingToBoil,
+                BrewPhase::Boil,
+                BrewPhase::HopStand,
+                BrewPhase::Finished,
+            ],
+            phases
+        );
+    }
+
+    #[test]
+    fn only_reports_a_mash_out_when_its_hot_enough() {
+        let mut session = BrewSession::new(Recipe {
+            delay: RecipeDelay::None,
+            mash_steps: vec![
+                MashStep {
+                    temperature: CentiCelsius::from_degrees(52),
+                    minutes: 15,
+                },
+                MashStep {
+                    temperature: CentiCelsius::from_degrees(66),
+                    minutes: 60,
+                },
+            ],
+            ..recipe()
+        });
+
+        update(&mut session, 0, 0, InteractionCode::None);
+        assert_eq!(BrewPhase::HeatingStrike, session.phase());
+
+        update(&mut session, 2, 30, InteractionCode::None);
+        assert_eq!(BrewPhase::MashStep(2), session.phase());
+    }
+
+    #[test]
+    fn paused_timers_arent_ramping() {
+        let mut session = BrewSession::new(recipe());
+        let paused = Notification::Timer(Timer {
+            active: false,
+            remaining_minutes: 30,
+            remaining_seconds: 0,
+            total_start_time: 61,
+        });
+
+        update(&mut session, 1, 45, InteractionCode::None);
+        session.handle_notification(&paused);
+        assert_eq!(BrewPhase::MashStep(1), session.phase());
+
+        update(&mut session, 4, 30, InteractionCode::None);
+        session.handle_notification(&paused);
+        assert_eq!(BrewPhase::Boil, session.phase());
+    }
+
+    #[test]
+    fn reports_time_remaining_and_boil_additions() {
+        let mut session = BrewSession::new(recipe());
+
+        update(&mut session, 1, 0, InteractionCode::None);
+        assert_eq!(None, session.time_remaining());
+
+        update(&mut session, 4, 61, InteractionCode::None);
+        assert_eq!(Some(Duration::from_secs(60 * 60)), session.time_remaining());
+        assert_eq!(Some(60), session.active_boil_addition());
+
+        update(&mut session, 4, 16, InteractionCode::None);
+        assert_eq!(Some(15), session.active_boil_addition());
+
+        session.handle_notification(&Notification::Timer(Timer {
+            active: true,
+            remaining_minutes: 1,
+            remaining_seconds: 30,
+            total_start_time: 61,
+        }));
+        assert_eq!(Some(Duration::from_secs(30)), session.time_remaining());
+        assert_eq!(Some(5), session.active_boil_addition());
+    }
+}
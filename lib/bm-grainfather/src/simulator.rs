@@ -0,0 +1,1022 @@
+//! A simulated Grainfather controller.
+//!
+//! The [Simulator](crate::simulator::Simulator) consumes the same command and recipe frames
+//! as a real controller, and produces the same stream of notifications, which makes it possible
+//! to exercise a client (and everything built on top of it) without any hardware.
+//!
+//! Time is driven explicitly by calling [tick](crate::simulator::Simulator::tick), and the wort
+//! temperature follows a simple thermal model: the element heats in proportion to its power
+//! output, and heat is lost in proportion to the difference from the ambient temperature.
+
+use crate::notifications::*;
+use crate::transport::{FrameHandler, Transport, TransportError};
+use crate::{
+    Command, Delay, DisconnectOption, InteractionCode, MashStep, Notification, Recipe, RecipeDecoder, RecipeDelay,
+    StepNumber, Units, Voltage,
+};
+use bm_units::CentiCelsius;
+use log::warn;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// The temperature of the simulated brewery, in degrees celsius.
+const AMBIENT_CELSIUS: f64 = 20.0;
+
+/// The temperature at which the simulated wort boils, in degrees celsius.
+const PHYSICAL_BOIL_CELSIUS: f64 = 100.0;
+
+/// The rate at which the element heats at full power, in degrees per second, this is
+/// roughly a 2kW element heating 25 litres.
+const HEATING_RATE: f64 = 2000.0 / (25.0 * 4186.0);
+
+/// The proportion of the difference from ambient lost every second.
+const HEAT_LOSS_RATE: f64 = 0.0002;
+
+/// Within this many degrees of the target, the element's power is reduced proportionally.
+const PROPORTIONAL_BAND: f64 = 2.0;
+
+/// A temperature within this many degrees of the target is considered to have been reached.
+const REACHED_TOLERANCE: f64 = 0.5;
+
+/// The amount manual power changes with each press of the arrows.
+const MANUAL_POWER_STEP: u8 = 10;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum TimerPurpose {
+    /// A timer started by the user outside of a recipe.
+    Manual,
+
+    /// Delays turning the heat on outside of a recipe.
+    DelayedHeat,
+
+    /// Delays the start of a recipe.
+    DelayedStart,
+
+    Mash,
+    Boil,
+    HopStand,
+}
+
+#[derive(Clone, Debug)]
+struct SimulatedTimer {
+    purpose: TimerPurpose,
+    total: Duration,
+    remaining: Duration,
+    paused: bool,
+}
+
+impl SimulatedTimer {
+    fn new(purpose: TimerPurpose, total: Duration) -> Self {
+        Self {
+            purpose,
+            total,
+            remaining: total,
+            paused: false,
+        }
+    }
+}
+
+/// A simulated Grainfather controller.
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    firmware_version: String,
+    voltage: Voltage,
+    units: Units,
+
+    current: f64,
+    target: CentiCelsius,
+    boil_temperature: CentiCelsius,
+    heat_active: bool,
+    pump_active: bool,
+    manual_power_mode: bool,
+    manual_power: u8,
+    power: u8,
+
+    timer: Option<SimulatedTimer>,
+
+    recipe_decoder: RecipeDecoder,
+    recipe: Option<Recipe>,
+    step_number: StepNumber,
+    step_ramp_active: bool,
+    grain_added: bool,
+    interaction: Option<InteractionCode>,
+    boil_additions: Vec<u8>,
+    boil_alert_displayed: bool,
+    sparge_water_alert_displayed: bool,
+    sparge_alert_mode: bool,
+    sparge_counter_active: bool,
+    sparge_progress: u8,
+    boil_control_active: bool,
+    recipe_interrupted: bool,
+
+    pending: Vec<Notification>,
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Self {
+            firmware_version: "sim-1.0".into(),
+            voltage: Voltage::V230,
+            units: Units::Celsius,
+
+            current: AMBIENT_CELSIUS,
+            target: CentiCelsius::from_celsius(AMBIENT_CELSIUS),
+            boil_temperature: CentiCelsius::from_degrees(100),
+            heat_active: false,
+            pump_active: false,
+            manual_power_mode: false,
+            manual_power: 100,
+            power: 0,
+
+            timer: None,
+
+            recipe_decoder: RecipeDecoder::new(),
+            recipe: None,
+            step_number: 0,
+            step_ramp_active: false,
+            grain_added: false,
+            interaction: None,
+            boil_additions: Vec::new(),
+            boil_alert_displayed: false,
+            sparge_water_alert_displayed: false,
+            sparge_alert_mode: true,
+            sparge_counter_active: true,
+            sparge_progress: 0,
+            boil_control_active: false,
+            recipe_interrupted: false,
+
+            pending: Vec::new(),
+        }
+    }
+
+    /// The temperature of the simulated wort.
+    pub fn current_temperature(&self) -> CentiCelsius {
+        CentiCelsius::from_celsius(self.current)
+    }
+
+    /// Sets the temperature of the simulated wort, for example to skip a long heat up.
+    pub fn set_current_temperature(&mut self, temperature: CentiCelsius) {
+        self.current = temperature.celsius();
+    }
+
+    /// The recipe currently loaded into the controller, if any.
+    pub fn recipe(&self) -> Option<&Recipe> {
+        self.recipe.as_ref()
+    }
+
+    /// Processes a frame written to the controller, which may be a command, or
+    /// part of a recipe upload. Returns any notifications emitted immediately
+    /// in response, such as replies to queries.
+    pub fn receive(&mut self, frame: &[u8]) -> Vec<Notification> {
+        if self.recipe_decoder.in_progress() || frame.first() == Some(&b'R') {
+            match self.recipe_decoder.push(frame) {
+                Ok(Some(recipe)) => self.load_recipe(recipe),
+                Ok(None) => {}
+                Err(err) => warn!("Simulator discarding recipe upload: {:?}", err),
+            }
+        } else {
+            match Command::try_from(frame) {
+                Ok(command) => self.handle_command(&command),
+                Err(err) => warn!("Simulator ignoring frame {:?}: {:?}", String::from_utf8_lossy(frame), err),
+            }
+        }
+
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Advances the simulation by the given amount of time, returning the notifications
+    /// emitted along the way, followed by the controller's regular status reports.
+    pub fn tick(&mut self, elapsed: Duration) -> Vec<Notification> {
+        let mut remaining = elapsed;
+
+        // Step a second at a time so that the thermal model and timers stay stable
+        while remaining > Duration::from_secs(0) {
+            let step = remaining.min(Duration::from_secs(1));
+            remaining -= step;
+
+            self.update_temperature(step);
+            self.update_timer(step);
+            self.update_recipe();
+        }
+
+        let mut notifications = std::mem::take(&mut self.pending);
+        notifications.extend(self.status());
+        notifications
+    }
+
+    /// Builds the regular status reports that the controller emits.
+    pub fn status(&self) -> Vec<Notification> {
+        let timer = match &self.timer {
+            Some(timer) => Timer {
+                active: !timer.paused,
+                remaining_minutes: (timer.remaining.as_secs() / 60) as u32 + 1,
+                remaining_seconds: (timer.remaining.as_secs() % 60) as u32,
+                total_start_time: (timer.total.as_secs() / 60) as u32 + 1,
+            },
+
+            None => Timer {
+                active: false,
+                remaining_minutes: 0,
+                remaining_seconds: 0,
+                total_start_time: 0,
+            },
+        };
+
+        vec![
+            Notification::Temp(Temp {
+                desired: self.target,
+                current: CentiCelsius::from_celsius((self.current * 10.0).round() / 10.0),
+            }),
+            Notification::Timer(timer),
+            Notification::Status1(Status1 {
+                heat_active: self.heat_active,
+                pump_active: self.pump_active,
+                auto_mode_active: self.recipe.is_some(),
+                step_ramp_active: self.step_ramp_active,
+                interaction_mode_active: self.interaction.is_some(),
+                interaction_code: self.interaction.clone().unwrap_or_default(),
+                step_number: self.step_number,
+                delayed_heat_mode_active: self.delayed_heat_mode_active(),
+            }),
+            Notification::Status2(Status2 {
+                heat_power_output_percentage: self.power,
+                timer_paused: self.timer.as_ref().map(|timer| timer.paused).unwrap_or(false),
+                step_mash_mode: false,
+                recipe_interrupted: self.recipe_interrupted,
+                manual_power_mode: self.manual_power_mode,
+                sparge_water_alert_displayed: self.sparge_water_alert_displayed,
+            }),
+        ]
+    }
+
+    /// Applies a command to the controller.
+    pub fn handle_command(&mut self, command: &Command) {
+        match command {
+            Command::Reset => {
+                let current = self.current;
+                *self = Self::new();
+                self.current = current;
+            }
+
+            Command::GetFirmwareVersion => {
+                self.pending.push(Notification::FirmwareVersion(FirmwareVersion {
+                    firmware_version: self.firmware_version.clone(),
+                }));
+            }
+
+            Command::GetVoltageAndUnits => {
+                self.pending.push(Notification::VoltageAndUnits(VoltageAndUnits {
+                    voltage: self.voltage.clone(),
+                    units: self.units.clone(),
+                }));
+            }
+
+            Command::GetBoilTemperature => {
+                self.pending.push(Notification::Boil(Boil {
+                    boil_temperature: self.boil_temperature,
+                }));
+            }
+
+            Command::ToggleHeatActive => self.heat_active = !self.heat_active,
+            Command::SetHeatActive(active) => self.heat_active = *active,
+            Command::TogglePumpActive => self.pump_active = !self.pump_active,
+            Command::SetPumpActive(active) => self.pump_active = *active,
+
+            Command::EnableDelayedHeatTimer {
+                minutes,
+                seconds,
+            } => {
+                let total = Duration::from_secs(u64::from(*minutes) * 60 + u64::from(*seconds));
+                self.heat_active = false;
+                self.timer = Some(SimulatedTimer::new(TimerPurpose::DelayedHeat, total));
+            }
+
+            Command::CancelActiveTimer => self.timer = None,
+
+            Command::UpdateActiveTimer(delay) => {
+                let remaining = match delay {
+                    Delay::Minutes(minutes) => Duration::from_secs(u64::from(*minutes) * 60),
+                    Delay::MinutesSeconds(minutes, seconds) => {
+                        Duration::from_secs(u64::from(*minutes) * 60 + u64::from(*seconds))
+                    }
+                };
+
+                match &mut self.timer {
+                    Some(timer) => {
+                        timer.remaining = remaining;
+                        timer.total = timer.total.max(remaining);
+                    }
+
+                    None => self.timer = Some(SimulatedTimer::new(TimerPurpose::Manual, remaining)),
+                }
+            }
+
+            Command::PauseOrResumeActiveTimer => {
+                if let Some(timer) = &mut self.timer {
+                    timer.paused = !timer.paused;
+                }
+            }
+
+            Command::IncrementTargetTemperature => {
+                if self.manual_power_mode {
+                    self.manual_power = self.manual_power.saturating_add(MANUAL_POWER_STEP).min(100);
+                } else {
+                    self.target = CentiCelsius::new(self.target.centi_celsius() + 100);
+                }
+            }
+
+            Command::DecrementTargetTemperature => {
+                if self.manual_power_mode {
+                    self.manual_power = self.manual_power.saturating_sub(MANUAL_POWER_STEP);
+                } else {
+                    self.target = CentiCelsius::new(self.target.centi_celsius() - 100);
+                }
+            }
+
+            Command::SetTargetTemperature(temperature) => self.target = *temperature,
+            Command::SetLocalBoilTemperature(temperature) => self.boil_temperature = *temperature,
+
+            Command::DismissAlert => {
+                self.dismiss_alert();
+            }
+
+            Command::PressSet => {
+                if self.dismiss_alert() {
+                    self.pending.push(Notification::Interaction(Interaction {
+                        interaction_code: InteractionCode::Dismiss,
+                    }));
+                } else if self.interaction.is_some() {
+                    self.complete_interaction();
+                } else if let Some(TimerPurpose::DelayedStart) = self.timer_purpose() {
+                    self.timer = None;
+                    self.start_mash_step(0);
+                }
+            }
+
+            Command::DisableSpargeWaterAlert => self.sparge_water_alert_displayed = false,
+            Command::ResetRecipeInterrupted => self.recipe_interrupted = false,
+
+            Command::CancelOrFinishSession => self.finish_session(),
+
+            Command::Disconnect(option) => match option {
+                DisconnectOption::ManualMode | DisconnectOption::CancelSession => self.finish_session(),
+                DisconnectOption::AutomaticMode => {}
+            },
+
+            Command::SetSpargeProgress(progress) => self.sparge_progress = *progress,
+
+            Command::UpdateStep {
+                step_number,
+                temperature,
+                time_minutes,
+            } => {
+                let index = usize::from(*step_number).wrapping_sub(1);
+
+                if let Some(step) = self.recipe.as_mut().and_then(|recipe| recipe.mash_steps.get_mut(index)) {
+                    step.temperature = *temperature;
+                    step.minutes = *time_minutes;
+
+                    if *step_number == self.step_number {
+                        self.target = *temperature;
+                    }
+                }
+            }
+
+            Command::SkipToStep {
+                step_number,
+                time_left_minutes,
+                time_left_seconds,
+                skip_ramp,
+                disable_add_grain,
+                ..
+            } => {
+                let time_left = Duration::from_secs(u64::from(*time_left_minutes) * 60 + u64::from(*time_left_seconds));
+                self.skip_to_step(*step_number, time_left, *skip_ramp, *disable_add_grain);
+            }
+
+            Command::InteractionComplete => self.complete_interaction(),
+
+            Command::SkipToInteraction(code) => match code {
+                InteractionCode::None => self.interaction = None,
+                other => self.start_interaction(other.clone()),
+            },
+
+            Command::SetSpargeCounterActive(active) => self.sparge_counter_active = *active,
+            Command::SetBoilControlActive(active) => self.boil_control_active = *active,
+            Command::SetManualPowerControlActive(active) => self.manual_power_mode = *active,
+            Command::SetSpargeAlertModeActive(active) => self.sparge_alert_mode = *active,
+        }
+    }
+
+    fn mash_step_count(&self) -> StepNumber {
+        self.recipe.as_ref().map(|recipe| recipe.mash_steps.len() as StepNumber).unwrap_or(0)
+    }
+
+    fn timer_purpose(&self) -> Option<TimerPurpose> {
+        self.timer.as_ref().map(|timer| timer.purpose)
+    }
+
+    fn delayed_heat_mode_active(&self) -> bool {
+        matches!(self.timer_purpose(), Some(TimerPurpose::DelayedHeat) | Some(TimerPurpose::DelayedStart))
+    }
+
+    fn load_recipe(&mut self, recipe: Recipe) {
+        self.finish_session();
+
+        self.sparge_alert_mode = recipe.show_sparge_alert;
+        self.sparge_counter_active = recipe.show_sparge_counter;
+
+        // Additions are prompted in the order they're made, i.e. longest time remaining first
+        self.boil_additions = recipe.boil_steps.clone();
+        self.boil_additions.sort_unstable_by(|a, b| b.cmp(a));
+
+        let delay = match recipe.delay {
+            RecipeDelay::MinutesSeconds(minutes, seconds) => {
+                Some(Duration::from_secs(u64::from(minutes) * 60 + u64::from(seconds)))
+            }
+            RecipeDelay::None => None,
+        };
+
+        let skip_start = recipe.skip_start;
+        self.recipe = Some(recipe);
+
+        match delay {
+            Some(delay) => self.timer = Some(SimulatedTimer::new(TimerPurpose::DelayedStart, delay)),
+            None if skip_start => self.skip_to_step(1, Duration::from_secs(0), true, true),
+            None => self.start_mash_step(0),
+        }
+    }
+
+    fn finish_session(&mut self) {
+        self.recipe = None;
+        self.recipe_decoder = RecipeDecoder::new();
+        self.step_number = 0;
+        self.step_ramp_active = false;
+        self.grain_added = false;
+        self.interaction = None;
+        self.timer = None;
+        self.boil_additions.clear();
+        self.boil_alert_displayed = false;
+        self.sparge_water_alert_displayed = false;
+        self.heat_active = false;
+        self.pump_active = false;
+    }
+
+    fn start_mash_step(&mut self, index: usize) {
+        let (step_count, temperature, strike_temperature, show_sparge_alert) = match &self.recipe {
+            Some(recipe) => match recipe.mash_steps.get(index) {
+                Some(MashStep {
+                    temperature,
+                    ..
+                }) => (recipe.mash_steps.len(), *temperature, recipe.strike_temperature, recipe.show_sparge_alert),
+                None => return self.start_interaction(InteractionCode::MashOutDoneStartSparge),
+            },
+            None => return,
+        };
+
+        self.step_number = (index + 1) as StepNumber;
+        self.target = match strike_temperature {
+            Some(strike_temperature) if index == 0 && !self.grain_added => strike_temperature,
+            _ => temperature,
+        };
+        self.step_ramp_active = true;
+        self.heat_active = true;
+        self.timer = None;
+
+        // Prompt to heat the sparge water as the final (mash out) step starts
+        if index + 1 == step_count && show_sparge_alert && self.sparge_alert_mode {
+            self.sparge_water_alert_displayed = true;
+            self.pending.push(Notification::PromptSpargeWater(PromptSpargeWater));
+        }
+    }
+
+    fn start_mash_timer(&mut self, time_left: Option<Duration>) {
+        let index = usize::from(self.step_number).wrapping_sub(1);
+
+        let minutes = match self.recipe.as_ref().and_then(|recipe| recipe.mash_steps.get(index)) {
+            Some(step) => step.minutes,
+            None => return,
+        };
+
+        let total = Duration::from_secs(u64::from(minutes) * 60);
+        let mut timer = SimulatedTimer::new(TimerPurpose::Mash, total);
+        timer.remaining = time_left.unwrap_or(total);
+        self.timer = Some(timer);
+    }
+
+    fn start_boil(&mut self, ramp: bool) {
+        self.step_number = self.mash_step_count() + 2;
+        self.target = self.boil_temperature;
+        self.heat_active = true;
+        self.timer = None;
+        self.step_ramp_active = ramp;
+
+        if !ramp {
+            self.start_boil_timer(None);
+        }
+    }
+
+    fn start_boil_timer(&mut self, time_left: Option<Duration>) {
+        let boil_time = self.recipe.as_ref().map(|recipe| recipe.boil_time).unwrap_or(0);
+        let total = Duration::from_secs(u64::from(boil_time) * 60);
+        let mut timer = SimulatedTimer::new(TimerPurpose::Boil, total);
+        timer.remaining = time_left.unwrap_or(total);
+        self.timer = Some(timer);
+        self.update_boil_additions();
+    }
+
+    fn finish_boil(&mut self) {
+        self.step_number = self.mash_step_count() + 3;
+        self.heat_active = false;
+        self.timer = None;
+
+        let hop_stand_time = self.recipe.as_ref().map(|recipe| recipe.hop_stand_time).unwrap_or(0);
+
+        if hop_stand_time > 0 {
+            let total = Duration::from_secs(u64::from(hop_stand_time) * 60);
+            self.timer = Some(SimulatedTimer::new(TimerPurpose::HopStand, total));
+        } else {
+            self.start_interaction(InteractionCode::BoilFinished);
+        }
+    }
+
+    fn skip_to_step(&mut self, step_number: StepNumber, time_left: Duration, skip_ramp: bool, disable_add_grain: bool) {
+        let mash_step_count = self.mash_step_count();
+
+        if self.recipe.is_none() {
+            return;
+        }
+
+        self.interaction = None;
+        self.step_ramp_active = false;
+
+        let time_left = if time_left > Duration::from_secs(0) {
+            Some(time_left)
+        } else {
+            None
+        };
+
+        if step_number == 0 {
+            self.step_number = 0;
+        } else if step_number <= mash_step_count {
+            self.grain_added = disable_add_grain || step_number > 1 || self.grain_added;
+            self.start_mash_step(usize::from(step_number) - 1);
+
+            if skip_ramp {
+                self.step_ramp_active = false;
+
+                if self.grain_added {
+                    self.start_mash_timer(time_left);
+                } else {
+                    self.start_interaction(InteractionCode::AddGrain);
+                }
+            }
+        } else if step_number == mash_step_count + 1 {
+            self.step_number = step_number;
+            self.timer = None;
+            self.start_interaction(InteractionCode::Sparge);
+        } else if step_number == mash_step_count + 2 {
+            self.start_boil(!skip_ramp);
+
+            if skip_ramp {
+                self.start_boil_timer(time_left);
+            }
+        } else {
+            self.finish_boil();
+        }
+    }
+
+    fn start_interaction(&mut self, code: InteractionCode) {
+        self.pending.push(Notification::Interaction(Interaction {
+            interaction_code: code.clone(),
+        }));
+
+        self.interaction = Some(code);
+    }
+
+    fn complete_interaction(&mut self) {
+        let code = match self.interaction.take() {
+            Some(code) => code,
+            None => return,
+        };
+
+        match code {
+            InteractionCode::SkipDelayedRecipe => {
+                self.timer = None;
+                self.start_mash_step(0);
+            }
+
+            InteractionCode::AddGrain => {
+                self.grain_added = true;
+                self.add_grain();
+                self.start_mash_timer(None);
+            }
+
+            InteractionCode::MashOutDoneStartSparge => {
+                self.step_number = self.mash_step_count() + 1;
+                self.start_interaction(InteractionCode::Sparge);
+            }
+
+            InteractionCode::Sparge => self.start_boil(true),
+
+            InteractionCode::BoilReached => self.start_boil_timer(None),
+
+            InteractionCode::BoilFinished => self.finish_session(),
+
+            _ => {}
+        }
+    }
+
+    /// Adding the grain to strike water cools it to the first mash step's temperature.
+    fn add_grain(&mut self) {
+        let first_step = self.recipe.as_ref().and_then(|recipe| match recipe.strike_temperature {
+            Some(_) => recipe.mash_steps.first(),
+            None => None,
+        });
+
+        if let Some(MashStep {
+            temperature,
+            ..
+        }) = first_step
+        {
+            self.target = *temperature;
+            self.current = temperature.celsius();
+        }
+    }
+
+    /// Dismisses the boil addition or sparge water alert, returning whether there was one to dismiss.
+    fn dismiss_alert(&mut self) -> bool {
+        if self.sparge_water_alert_displayed {
+            self.sparge_water_alert_displayed = false;
+            return true;
+        }
+
+        if self.boil_alert_displayed {
+            self.boil_alert_displayed = false;
+            return true;
+        }
+
+        false
+    }
+
+    fn update_temperature(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let target = self.target.celsius();
+
+        self.power = if !self.heat_active {
+            0
+        } else if self.manual_power_mode {
+            self.manual_power
+        } else {
+            // Hold the current temperature against losses, and add power in proportion to
+            // how far away the target is
+            let holding = (self.current - AMBIENT_CELSIUS) * HEAT_LOSS_RATE / HEATING_RATE;
+            let proportional = (target - self.current) / PROPORTIONAL_BAND;
+
+            ((holding + proportional).clamp(0.0, 1.0) * 100.0).round() as u8
+        };
+
+        let heating = f64::from(self.power) / 100.0 * HEATING_RATE * seconds;
+        let loss = (self.current - AMBIENT_CELSIUS) * HEAT_LOSS_RATE * seconds;
+
+        self.current = (self.current + heating - loss).min(PHYSICAL_BOIL_CELSIUS);
+    }
+
+    fn update_timer(&mut self, elapsed: Duration) {
+        let timer = match &mut self.timer {
+            Some(timer) if !timer.paused => timer,
+            _ => return,
+        };
+
+        timer.remaining = timer.remaining.checked_sub(elapsed).unwrap_or_default();
+
+        if timer.remaining > Duration::from_secs(0) {
+            if timer.purpose == TimerPurpose::Boil {
+                self.update_boil_additions();
+            }
+
+            return;
+        }
+
+        let purpose = timer.purpose;
+        self.timer = None;
+
+        match purpose {
+            TimerPurpose::Manual => {}
+
+            TimerPurpose::DelayedHeat => self.heat_active = true,
+
+            TimerPurpose::DelayedStart => self.start_mash_step(0),
+
+            TimerPurpose::Mash => self.start_mash_step(usize::from(self.step_number)),
+
+            TimerPurpose::Boil => {
+                self.update_boil_additions();
+                self.finish_boil();
+            }
+
+            TimerPurpose::HopStand => self.start_interaction(InteractionCode::BoilFinished),
+        }
+    }
+
+    /// Prompts for any boil additions that are now due.
+    fn update_boil_additions(&mut self) {
+        let remaining = match &self.timer {
+            Some(timer) if timer.purpose == TimerPurpose::Boil => timer.remaining,
+            _ => return,
+        };
+
+        while let Some(addition) = self.boil_additions.first() {
+            if Duration::from_secs(u64::from(*addition) * 60) < remaining {
+                break;
+            }
+
+            self.boil_additions.remove(0);
+            self.boil_alert_displayed = true;
+            self.pending.push(Notification::PromptBoilAddition(PromptBoilAddition));
+        }
+    }
+
+    fn update_recipe(&mut self) {
+        if self.recipe.is_none() || !self.step_ramp_active {
+            return;
+        }
+
+        if self.current < self.target.celsius() - REACHED_TOLERANCE {
+            return;
+        }
+
+        self.step_ramp_active = false;
+        self.pending.push(Notification::TemperatureReached(TemperatureReached));
+
+        if self.step_number == self.mash_step_count() + 2 {
+            self.start_interaction(InteractionCode::BoilReached);
+        } else if self.step_number == 1 && !self.grain_added {
+            self.start_interaction(InteractionCode::AddGrain);
+        } else {
+            self.start_mash_timer(None);
+        }
+    }
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [transport](crate::transport::Transport) that delivers frames to a [Simulator](crate::simulator::Simulator)
+/// rather than a real controller.
+pub struct SimulatedTransport {
+    simulator: Arc<Mutex<Simulator>>,
+    handler: Arc<Mutex<Option<FrameHandler>>>,
+    outbox: Arc<Mutex<Vec<Notification>>>,
+    time_scale: f64,
+    running: AtomicBool,
+}
+
+impl SimulatedTransport {
+    /// How often the simulator emits its regular status reports.
+    const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// How often replies to commands are delivered.
+    const DELIVERY_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Constructs a transport for the given simulator, simulated time passes `time_scale`
+    /// times faster than real time.
+    pub fn new(simulator: Simulator, time_scale: f64) -> Self {
+        Self {
+            simulator: Arc::new(Mutex::new(simulator)),
+            handler: Arc::new(Mutex::new(None)),
+            outbox: Arc::new(Mutex::new(Vec::new())),
+            time_scale,
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Runs the simulation on its own thread until the transport is dropped, all
+    /// notifications are delivered from this thread so that the handler may issue
+    /// commands without deadlocking.
+    fn run(
+        weak_simulator: Weak<Mutex<Simulator>>,
+        handler: Arc<Mutex<Option<FrameHandler>>>,
+        outbox: Arc<Mutex<Vec<Notification>>>,
+        time_scale: f64,
+    ) {
+        let mut last_tick = Instant::now();
+
+        loop {
+            std::thread::sleep(Self::DELIVERY_INTERVAL);
+
+            let simulator = match weak_simulator.upgrade() {
+                Some(simulator) => simulator,
+                None => return,
+            };
+
+            let mut notifications = std::mem::take(&mut *outbox.lock().unwrap());
+
+            if last_tick.elapsed() >= Self::TICK_INTERVAL {
+                let elapsed = last_tick.elapsed().mul_f64(time_scale);
+                last_tick = Instant::now();
+                notifications.extend(simulator.lock().unwrap().tick(elapsed));
+            }
+
+            if let Some(handler) = handler.lock().unwrap().as_mut() {
+                for notification in notifications {
+                    match notification.to_vec() {
+                        Ok(frame) => handler(frame.as_slice()),
+                        Err(err) => warn!("Simulator unable to encode notification {:?}: {:?}", notification, err),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Transport for SimulatedTransport {
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), TransportError> {
+        let replies = self.simulator.lock().unwrap().receive(data);
+        self.outbox.lock().unwrap().extend(replies);
+        Ok(())
+    }
+
+    fn on_notification(&self, handler: FrameHandler) {
+        *self.handler.lock().unwrap() = Some(handler);
+    }
+
+    fn subscribe(&self) -> Result<(), TransportError> {
+        if !self.running.swap(true, Ordering::SeqCst) {
+            let simulator = Arc::downgrade(&self.simulator);
+            let handler = self.handler.clone();
+            let outbox = self.outbox.clone();
+            let time_scale = self.time_scale;
+
+            std::thread::spawn(move || Self::run(simulator, handler, outbox, time_scale));
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SimulatedTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimulatedTransport")
+            .field("simulator", &self.simulator)
+            .field("time_scale", &self.time_scale)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(simulator: &mut Simulator, command: Command) -> Vec<Notification> {
+        simulator.receive(&command.to_vec().unwrap())
+    }
+
+    fn status1(simulator: &Simulator) -> Status1 {
+        match &simulator.status()[2] {
+            Notification::Status1(status) => status.clone(),
+            other => panic!("Unexpected notification {:?}", other),
+        }
+    }
+
+    /// Ticks the simulator a second at a time until the predicate is satisfied.
+    fn run_until<F>(simulator: &mut Simulator, max_minutes: u64, mut predicate: F) -> Vec<Notification>
+    where
+        F: FnMut(&Simulator) -> bool,
+    {
+        let mut notifications = Vec::new();
+
+        for _ in 0..(max_minutes * 60) {
+            notifications.extend(simulator.tick(Duration::from_secs(1)));
+
+            if predicate(simulator) {
+                return notifications;
+            }
+        }
+
+        panic!("Condition not reached within {} minutes: {:?}", max_minutes, simulator);
+    }
+
+    fn interaction(simulator: &Simulator) -> Option<InteractionCode> {
+        simulator.interaction.clone()
+    }
+
+    #[test]
+    fn replies_to_queries() {
+        let mut simulator = Simulator::new();
+
+        assert!(matches!(
+            send(&mut simulator, Command::GetFirmwareVersion).as_slice(),
+            [Notification::FirmwareVersion(_)]
+        ));
+
+        assert_eq!(
+            vec![Notification::Boil(Boil {
+                boil_temperature: CentiCelsius::from_degrees(100)
+            })],
+            send(&mut simulator, Command::GetBoilTemperature)
+        );
+    }
+
+    #[test]
+    fn heats_to_and_holds_target() {
+        let mut simulator = Simulator::new();
+        send(&mut simulator, Command::SetTargetTemperature(CentiCelsius::from_degrees(40)));
+        send(&mut simulator, Command::SetHeatActive(true));
+
+        run_until(&mut simulator, 60, |simulator| simulator.current >= 39.5);
+        simulator.tick(Duration::from_secs(30 * 60));
+
+        assert!((simulator.current - 40.0).abs() < 1.0, "temperature was {}", simulator.current);
+
+        send(&mut simulator, Command::SetHeatActive(false));
+        simulator.tick(Duration::from_secs(60 * 60));
+        assert!(simulator.current < 39.0);
+    }
+
+    #[test]
+    fn runs_a_recipe() {
+        let recipe = Recipe {
+            name: "Simulated".into(),
+            boil_time: 2,
+            hop_stand_time: 1,
+            boil_steps: vec![2, 1],
+            mash_steps: vec![
+                MashStep {
+                    temperature: CentiCelsius::from_degrees(30),
+                    minutes: 1,
+                },
+                MashStep {
+                    temperature: CentiCelsius::from_degrees(35),
+                    minutes: 1,
+                },
+            ],
+            strike_temperature: Some(CentiCelsius::from_degrees(33)),
+            ..Recipe::default()
+        };
+
+        let mut simulator = Simulator::new();
+
+        for frame in recipe.to_commands().unwrap() {
+            simulator.receive(&frame);
+        }
+
+        assert_eq!(Some(&recipe), simulator.recipe());
+        assert!(status1(&simulator).auto_mode_active);
+        assert_eq!(1, status1(&simulator).step_number);
+
+        run_until(&mut simulator, 30, |simulator| interaction(simulator) == Some(InteractionCode::AddGrain));
+        assert!(simulator.current_temperature() >= CentiCelsius::new(3250));
+        send(&mut simulator, Command::PressSet);
+        assert_eq!(CentiCelsius::from_degrees(30), simulator.target);
+
+        let notifications = run_until(&mut simulator, 30, |simulator| simulator.step_number == 2);
+        assert!(notifications.contains(&Notification::PromptSpargeWater(PromptSpargeWater)));
+        assert!(status1(&simulator).step_ramp_active);
+
+        // The first press dismisses the sparge water alert, rather than an interaction
+        assert_eq!(
+            vec![Notification::Interaction(Interaction {
+                interaction_code: InteractionCode::Dismiss
+            })],
+            send(&mut simulator, Command::PressSet)
+        );
+
+        run_until(&mut simulator, 30, |simulator| {
+            interaction(simulator) == Some(InteractionCode::MashOutDoneStartSparge)
+        });
+        send(&mut simulator, Command::PressSet);
+        assert_eq!(Some(InteractionCode::Sparge), interaction(&simulator));
+        assert_eq!(3, simulator.step_number);
+
+        send(&mut simulator, Command::PressSet);
+        assert_eq!(4, simulator.step_number);
+
+        // Skip most of the heat up to the boil
+        simulator.set_current_temperature(CentiCelsius::from_degrees(95));
+        run_until(&mut simulator, 30, |simulator| interaction(simulator) == Some(InteractionCode::BoilReached));
+
+        // The first addition is due as soon as the boil starts
+        let mut notifications = send(&mut simulator, Command::PressSet);
+        notifications.extend(run_until(&mut simulator, 30, |simulator| simulator.step_number == 5));
+        let additions = notifications.iter().filter(|n| **n == Notification::PromptBoilAddition(PromptBoilAddition));
+        assert_eq!(2, additions.count());
+        assert!(!simulator.heat_active);
+
+        run_until(&mut simulator, 30, |simulator| interaction(simulator) == Some(InteractionCode::BoilFinished));
+        send(&mut simulator, Command::PressSet);
+        send(&mut simulator, Command::PressSet);
+
+        assert!(!status1(&simulator).auto_mode_active);
+        assert_eq!(0, status1(&simulator).step_number);
+    }
+}
@@ -0,0 +1,164 @@
+//! Tapping the conversation between a client and a controller as it happens.
+//!
+//! Unlike a [capture](crate::capture), which records raw notification data as it's
+//! received, a trace reports each frame alongside the command or notification it
+//! decodes to, so that the conversation can be followed live.
+
+use crate::capture::Direction;
+use crate::transport::{FrameHandler, Transport, TransportError};
+use crate::{Command, Notification};
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// What a traced frame decodes to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decoded {
+    Command(Command),
+    Notification(Notification),
+
+    /// The frame isn't a single command, e.g. it's part of a recipe.
+    Other,
+}
+
+/// A frame exchanged with the controller.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub timestamp: SystemTime,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+    pub decoded: Decoded,
+}
+
+pub type TraceHandler = Box<dyn FnMut(&TraceEntry) + Send>;
+
+/// Passes traced frames to the handler, if there is one, frames are only decoded
+/// while there's a handler to receive them.
+#[derive(Default)]
+pub(crate) struct Tracer {
+    handler: Mutex<Option<TraceHandler>>,
+}
+
+impl Tracer {
+    pub fn set_handler(&self, handler: Option<TraceHandler>) {
+        *self.handler.lock().unwrap() = handler;
+    }
+
+    pub fn sent(&self, data: &[u8]) {
+        self.trace(Direction::Sent, data, || match Command::try_from(data) {
+            Ok(command) => Decoded::Command(command),
+            Err(_) => Decoded::Other,
+        });
+    }
+
+    pub fn received(&self, notification: &Notification, data: &[u8]) {
+        self.trace(Direction::Received, data, || Decoded::Notification(notification.clone()));
+    }
+
+    fn trace<F>(&self, direction: Direction, data: &[u8], decode: F)
+    where
+        F: FnOnce() -> Decoded,
+    {
+        if let Some(handler) = self.handler.lock().unwrap().as_mut() {
+            handler(&TraceEntry {
+                timestamp: SystemTime::now(),
+                direction,
+                data: data.to_vec(),
+                decoded: decode(),
+            });
+        }
+    }
+}
+
+impl std::fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tracer").field("active", &self.handler.lock().unwrap().is_some()).finish()
+    }
+}
+
+/// A transport that traces every frame written through another transport, notifications
+/// are traced by the client once they've been framed.
+#[derive(Debug)]
+pub(crate) struct TracingTransport {
+    inner: Arc<dyn Transport>,
+    tracer: Arc<Tracer>,
+}
+
+impl TracingTransport {
+    pub fn new(inner: Arc<dyn Transport>, tracer: Arc<Tracer>) -> Self {
+        Self {
+            inner,
+            tracer,
+        }
+    }
+}
+
+impl Transport for TracingTransport {
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), TransportError> {
+        let result = self.inner.write(data);
+
+        if result.is_ok() {
+            self.tracer.sent(data);
+        }
+
+        result
+    }
+
+    fn on_notification(&self, handler: FrameHandler) {
+        self.inner.on_notification(handler)
+    }
+
+    fn subscribe(&self) -> Result<(), TransportError> {
+        self.inner.subscribe()
+    }
+
+    fn connect(&self) -> Result<(), TransportError> {
+        self.inner.connect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifications::FirmwareVersion;
+    use crate::transport::LoopbackTransport;
+    use crate::Client;
+    use std::sync::mpsc;
+
+    #[test]
+    fn traces_decoded_frames() {
+        let (transport, controller) = LoopbackTransport::new();
+        let client = Client::from_transport(transport);
+        let (sender, receiver) = mpsc::channel();
+
+        client.set_tracer(Some(Box::new(move |entry| sender.send(entry.clone()).unwrap())));
+        client.subscribe(Box::new(|_| {})).unwrap();
+
+        let notification = Notification::FirmwareVersion(FirmwareVersion {
+            firmware_version: "1.2.3".to_string(),
+        });
+        let frame = notification.to_vec().unwrap();
+
+        client.command(&Command::GetFirmwareVersion).unwrap();
+        assert!(controller.notify(&frame[..5]));
+        assert!(controller.notify(&frame[5..]));
+
+        let entries = receiver.try_iter().collect::<Vec<_>>();
+
+        assert_eq!(2, entries.len());
+        assert_eq!(Direction::Sent, entries[0].direction);
+        assert_eq!(Command::GetFirmwareVersion.to_vec().unwrap(), entries[0].data);
+        assert_eq!(Decoded::Command(Command::GetFirmwareVersion), entries[0].decoded);
+        assert_eq!(Direction::Received, entries[1].direction);
+        assert_eq!(frame, entries[1].data);
+        assert_eq!(Decoded::Notification(notification), entries[1].decoded);
+
+        client.set_tracer(None);
+        client.command(&Command::GetFirmwareVersion).unwrap();
+        assert!(receiver.try_recv().is_err());
+    }
+}
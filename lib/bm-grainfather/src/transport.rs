@@ -0,0 +1,117 @@
+//! The transports that carry frames between a [Client](crate::Client) and a controller.
+//!
+//! A transport deals only in raw frames, writing command frames to the controller and
+//! delivering the chunks of notification data that it emits. Encoding, decoding and
+//! framing are left to the client, so every transport shares the same parsing.
+//!
+//! As well as the btleplug transport (available with the `btleplug` feature), there's an
+//! [in-memory loopback](crate::transport::LoopbackTransport) for testing, and a
+//! [TCP transport](crate::transport::TcpTransport) for reaching a controller through a
+//! remote bluetooth gateway.
+
+mod loopback;
+pub use loopback::*;
+
+mod tcp;
+pub use tcp::*;
+
+/// Receives chunks of notification data from a transport.
+pub type FrameHandler = Box<dyn FnMut(&[u8]) + Send>;
+
+/// Possible errors encountered by a transport.
+#[derive(Debug)]
+pub enum TransportError {
+    /// The transport isn't connected to the controller.
+    NotConnected,
+
+    /// The frame is too long to be carried by the transport.
+    FrameTooLong(usize),
+
+    Io(std::io::Error),
+
+    /// An error specific to the underlying transport.
+    Other(String),
+}
+
+impl From<std::io::Error> for TransportError {
+    fn from(other: std::io::Error) -> Self {
+        Self::Io(other)
+    }
+}
+
+/// Carries frames to and from a controller.
+pub trait Transport: Send + Sync + std::fmt::Debug {
+    /// Determines whether the transport is connected to the controller.
+    fn is_connected(&self) -> bool;
+
+    /// Writes a single frame to the controller.
+    fn write(&self, data: &[u8]) -> Result<(), TransportError>;
+
+    /// Sets the handler that receives notification data from the controller, this
+    /// replaces any previous handler. Data is only delivered after subscribing.
+    fn on_notification(&self, handler: FrameHandler);
+
+    /// Starts the delivery of notification data to the handler.
+    fn subscribe(&self) -> Result<(), TransportError>;
+
+    /// Re-establishes the connection to the controller once it has been lost, the
+    /// transport must be subscribed again afterwards. By default, transports can't
+    /// reconnect, and this only succeeds if the transport is still connected.
+    fn connect(&self) -> Result<(), TransportError> {
+        if self.is_connected() {
+            Ok(())
+        } else {
+            Err(TransportError::NotConnected)
+        }
+    }
+}
+
+impl<T> Transport for Box<T>
+where
+    T: Transport + ?Sized,
+{
+    fn is_connected(&self) -> bool {
+        (**self).is_connected()
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), TransportError> {
+        (**self).write(data)
+    }
+
+    fn on_notification(&self, handler: FrameHandler) {
+        (**self).on_notification(handler)
+    }
+
+    fn subscribe(&self) -> Result<(), TransportError> {
+        (**self).subscribe()
+    }
+
+    fn connect(&self) -> Result<(), TransportError> {
+        (**self).connect()
+    }
+}
+
+impl<T> Transport for std::sync::Arc<T>
+where
+    T: Transport + ?Sized,
+{
+    fn is_connected(&self) -> bool {
+        (**self).is_connected()
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), TransportError> {
+        (**self).write(data)
+    }
+
+    fn on_notification(&self, handler: FrameHandler) {
+        (**self).on_notification(handler)
+    }
+
+    fn subscribe(&self) -> Result<(), TransportError> {
+        (**self).subscribe()
+    }
+
+    fn connect(&self) -> Result<(), TransportError> {
+        (**self).connect()
+    }
+}
@@ -0,0 +1,154 @@
+use super::{FrameHandler, Transport, TransportError};
+use std::sync::{Arc, Mutex};
+
+type Responder = Box<dyn FnMut(&[u8]) -> Vec<Vec<u8>> + Send>;
+
+struct LoopbackState {
+    connected: bool,
+    subscribed: bool,
+    written: Vec<Vec<u8>>,
+    handler: Option<FrameHandler>,
+    responder: Option<Responder>,
+}
+
+/// An in-memory transport, where the "controller" is driven by a
+/// [LoopbackHandle](crate::transport::LoopbackHandle). This is mostly useful for testing.
+#[derive(Clone)]
+pub struct LoopbackTransport {
+    state: Arc<Mutex<LoopbackState>>,
+}
+
+/// Plays the part of the controller for a [LoopbackTransport](crate::transport::LoopbackTransport).
+#[derive(Clone)]
+pub struct LoopbackHandle {
+    state: Arc<Mutex<LoopbackState>>,
+}
+
+impl LoopbackTransport {
+    pub fn new() -> (Self, LoopbackHandle) {
+        let state = Arc::new(Mutex::new(LoopbackState {
+            connected: true,
+            subscribed: false,
+            written: Vec::new(),
+            handler: None,
+            responder: None,
+        }));
+
+        let handle = LoopbackHandle {
+            state: state.clone(),
+        };
+
+        (
+            Self {
+                state,
+            },
+            handle,
+        )
+    }
+}
+
+impl LoopbackHandle {
+    /// Sets a function that replies to each frame written to the transport with any
+    /// number of chunks of notification data.
+    pub fn respond_with<F>(&self, responder: F)
+    where
+        F: FnMut(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+    {
+        self.state.lock().unwrap().responder = Some(Box::new(responder));
+    }
+
+    /// Delivers a chunk of notification data, returning whether there was a subscribed
+    /// handler to receive it.
+    pub fn notify(&self, data: &[u8]) -> bool {
+        deliver(&self.state, &[data.to_vec()])
+    }
+
+    /// Takes the frames written to the transport since they were last taken.
+    pub fn take_written(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.state.lock().unwrap().written)
+    }
+
+    /// Simulates the controller connecting or disconnecting, writes to a
+    /// disconnected transport fail.
+    pub fn set_connected(&self, connected: bool) {
+        self.state.lock().unwrap().connected = connected;
+    }
+}
+
+/// Delivers notification data to the handler without holding the lock, so that
+/// the handler is free to write to the transport.
+fn deliver(state: &Mutex<LoopbackState>, chunks: &[Vec<u8>]) -> bool {
+    let mut handler = {
+        let mut state = state.lock().unwrap();
+
+        match (state.subscribed, state.handler.take()) {
+            (true, Some(handler)) => handler,
+            (_, handler) => {
+                state.handler = handler;
+                return false;
+            }
+        }
+    };
+
+    for chunk in chunks {
+        handler(chunk);
+    }
+
+    let mut state = state.lock().unwrap();
+
+    // Keep the handler, unless it was replaced while it was running
+    if state.handler.is_none() {
+        state.handler = Some(handler);
+    }
+
+    true
+}
+
+impl Transport for LoopbackTransport {
+    fn is_connected(&self) -> bool {
+        self.state.lock().unwrap().connected
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), TransportError> {
+        let replies = {
+            let mut state = self.state.lock().unwrap();
+
+            if !state.connected {
+                return Err(TransportError::NotConnected);
+            }
+
+            state.written.push(data.to_vec());
+            state.responder.as_mut().map(|responder| responder(data)).unwrap_or_default()
+        };
+
+        deliver(&self.state, &replies);
+        Ok(())
+    }
+
+    fn on_notification(&self, handler: FrameHandler) {
+        self.state.lock().unwrap().handler = Some(handler);
+    }
+
+    fn subscribe(&self) -> Result<(), TransportError> {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.connected {
+            return Err(TransportError::NotConnected);
+        }
+
+        state.subscribed = true;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for LoopbackTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state.lock().unwrap();
+
+        f.debug_struct("LoopbackTransport")
+            .field("connected", &state.connected)
+            .field("subscribed", &state.subscribed)
+            .field("written", &state.written.len())
+            .finish()
+    }
+}
@@ -0,0 +1,327 @@
+//! Carries frames over TCP, to reach a controller through a remote bluetooth gateway.
+//!
+//! In both directions, each frame is sent as a single byte length followed by the
+//! frame's bytes. The gateway forwards frames it receives to the controller, and sends
+//! each chunk of notification data it receives from the controller.
+
+use super::{FrameHandler, Transport, TransportError};
+use log::warn;
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A transport connected to a bluetooth gateway over TCP.
+pub struct TcpTransport {
+    addr: SocketAddr,
+    writer: Mutex<TcpStream>,
+    reader: Mutex<Option<TcpStream>>,
+    handler: Arc<Mutex<Option<FrameHandler>>>,
+    connected: Arc<AtomicBool>,
+    subscribed: AtomicBool,
+
+    /// Counts the connections made, so that the reader of a connection that has been
+    /// replaced doesn't mark its replacement as disconnected as it finishes.
+    generation: Arc<Mutex<u64>>,
+}
+
+impl TcpTransport {
+    pub fn connect<A>(addr: A) -> Result<Self, TransportError>
+    where
+        A: ToSocketAddrs,
+    {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            addr: stream.peer_addr()?,
+            reader: Mutex::new(Some(stream.try_clone()?)),
+            writer: Mutex::new(stream),
+            handler: Arc::new(Mutex::new(None)),
+            connected: Arc::new(AtomicBool::new(true)),
+            subscribed: AtomicBool::new(false),
+            generation: Arc::new(Mutex::new(0)),
+        })
+    }
+
+    /// Starts delivering notification data from the current connection, unless that's
+    /// already happening.
+    fn start_reader(&self) {
+        let (mut reader, reader_generation) = {
+            let generation = self.generation.lock().unwrap();
+
+            match self.reader.lock().unwrap().take() {
+                Some(reader) => (reader, *generation),
+                None => return,
+            }
+        };
+
+        let handler = self.handler.clone();
+        let connected = self.connected.clone();
+        let generation = self.generation.clone();
+
+        std::thread::spawn(move || {
+            loop {
+                match read_frame(&mut reader) {
+                    Ok(Some(frame)) => {
+                        if let Some(handler) = handler.lock().unwrap().as_mut() {
+                            handler(&frame);
+                        }
+                    }
+
+                    Ok(None) => break,
+
+                    Err(err) => {
+                        warn!("Lost connection to the bluetooth gateway: {:?}", err);
+                        break;
+                    }
+                }
+            }
+
+            if *generation.lock().unwrap() == reader_generation {
+                connected.store(false, Ordering::SeqCst);
+            }
+        });
+    }
+}
+
+impl Transport for TcpTransport {
+    fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), TransportError> {
+        if !self.is_connected() {
+            return Err(TransportError::NotConnected);
+        }
+
+        let result = write_frame(&mut *self.writer.lock().unwrap(), data);
+
+        if result.is_err() {
+            self.connected.store(false, Ordering::SeqCst);
+        }
+
+        result
+    }
+
+    fn on_notification(&self, handler: FrameHandler) {
+        *self.handler.lock().unwrap() = Some(handler);
+    }
+
+    fn subscribe(&self) -> Result<(), TransportError> {
+        self.subscribed.store(true, Ordering::SeqCst);
+        self.start_reader();
+
+        Ok(())
+    }
+
+    /// Connects to the gateway again, at the address it was originally reached on, closing
+    /// the previous connection. Notification data is delivered from the new connection
+    /// straight away if the transport was subscribed.
+    fn connect(&self) -> Result<(), TransportError> {
+        if self.is_connected() {
+            return Ok(());
+        }
+
+        let stream = TcpStream::connect(self.addr)?;
+        stream.set_nodelay(true)?;
+
+        let reader = stream.try_clone()?;
+
+        {
+            let mut generation = self.generation.lock().unwrap();
+            let mut writer = self.writer.lock().unwrap();
+
+            // The previous connection may only have failed to write, which leaves its reader
+            // waiting, shutting it down releases the reader
+            let _ = writer.shutdown(Shutdown::Both);
+
+            *generation += 1;
+            *writer = stream;
+            *self.reader.lock().unwrap() = Some(reader);
+            self.connected.store(true, Ordering::SeqCst);
+        }
+
+        if self.subscribed.load(Ordering::SeqCst) {
+            self.start_reader();
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for TcpTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcpTransport").field("addr", &self.addr).field("connected", &self.is_connected()).finish()
+    }
+}
+
+/// Serves a transport to a single TCP connection, this is the gateway end of a
+/// [TcpTransport](crate::transport::TcpTransport). This returns once the remote end
+/// disconnects.
+pub fn serve_connection<T>(stream: TcpStream, transport: &T) -> Result<(), TransportError>
+where
+    T: Transport + ?Sized,
+{
+    stream.set_nodelay(true)?;
+
+    let mut notification_stream = stream.try_clone()?;
+
+    transport.on_notification(Box::new(move |data| {
+        if let Err(err) = write_frame(&mut notification_stream, data) {
+            warn!("Unable to forward notification data to the gateway client: {:?}", err);
+        }
+    }));
+
+    transport.subscribe()?;
+
+    let mut stream = stream;
+
+    while let Some(frame) = read_frame(&mut stream)? {
+        transport.write(&frame)?;
+    }
+
+    Ok(())
+}
+
+fn write_frame<W>(writer: &mut W, data: &[u8]) -> Result<(), TransportError>
+where
+    W: Write,
+{
+    if data.len() > usize::from(u8::MAX) {
+        return Err(TransportError::FrameTooLong(data.len()));
+    }
+
+    let mut message = Vec::with_capacity(data.len() + 1);
+    message.push(data.len() as u8);
+    message.extend_from_slice(data);
+
+    writer.write_all(&message)?;
+    Ok(())
+}
+
+/// Reads the next frame, or `None` if the stream has been closed between frames.
+fn read_frame<R>(reader: &mut R) -> Result<Option<Vec<u8>>, TransportError>
+where
+    R: Read,
+{
+    let mut len = [0u8; 1];
+
+    if reader.read(&mut len)? == 0 {
+        return Ok(None);
+    }
+
+    let mut frame = vec![0u8; usize::from(len[0])];
+    reader.read_exact(&mut frame)?;
+
+    Ok(Some(frame))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::LoopbackTransport;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn bridges_a_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (loopback, controller) = LoopbackTransport::new();
+        controller.respond_with(|frame| vec![frame.iter().rev().copied().collect()]);
+
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_connection(stream, &loopback).unwrap();
+        });
+
+        let transport = TcpTransport::connect(addr).unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        transport.on_notification(Box::new(move |data| sender.send(data.to_vec()).unwrap()));
+        transport.subscribe().unwrap();
+        transport.write(b"abc").unwrap();
+
+        assert_eq!(b"cba".to_vec(), receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+        assert_eq!(vec![b"abc".to_vec()], controller.take_written());
+        assert!(matches!(transport.write(&[0; 256]), Err(TransportError::FrameTooLong(256))));
+    }
+
+    #[test]
+    fn reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            // The first connection is dropped straight away, the second is served
+            drop(listener.accept().unwrap());
+
+            let (loopback, controller) = LoopbackTransport::new();
+            controller.respond_with(|frame| vec![frame.to_vec()]);
+
+            let (stream, _) = listener.accept().unwrap();
+            serve_connection(stream, &loopback).unwrap();
+        });
+
+        let transport = TcpTransport::connect(addr).unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        transport.on_notification(Box::new(move |data| sender.send(data.to_vec()).unwrap()));
+        transport.subscribe().unwrap();
+
+        let started = std::time::Instant::now();
+
+        while transport.is_connected() {
+            assert!(started.elapsed() < Duration::from_secs(5), "The dropped connection wasn't detected");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(matches!(transport.write(b"abc"), Err(TransportError::NotConnected)));
+
+        transport.connect().unwrap();
+        transport.subscribe().unwrap();
+        transport.write(b"abc").unwrap();
+
+        assert_eq!(b"abc".to_vec(), receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+    }
+
+    #[test]
+    fn ignores_replaced_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            // The first connection is only dropped once the second has been made
+            let (first, _) = listener.accept().unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            drop(first);
+
+            let (loopback, controller) = LoopbackTransport::new();
+            controller.respond_with(|frame| vec![frame.to_vec()]);
+
+            serve_connection(stream, &loopback).unwrap();
+        });
+
+        let transport = TcpTransport::connect(addr).unwrap();
+        let (sender, receiver) = mpsc::channel();
+
+        transport.on_notification(Box::new(move |data| sender.send(data.to_vec()).unwrap()));
+        transport.subscribe().unwrap();
+
+        // As if a write had failed, which leaves the first connection's reader waiting
+        transport.connected.store(false, Ordering::SeqCst);
+        transport.connect().unwrap();
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(transport.is_connected());
+
+        // The new connection delivers notifications without subscribing again
+        transport.write(b"abc").unwrap();
+
+        assert_eq!(b"abc".to_vec(), receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+        assert!(transport.is_connected());
+    }
+}
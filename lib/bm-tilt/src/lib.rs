@@ -1,4 +1,5 @@
 use bm_bluetooth::*;
+use bm_units::{Fahrenheit, SpecificGravity};
 use std::convert::{TryFrom, TryInto};
 
 const TILT_RED: u128 = 0xA495BB10C5B14B44B5121370F02D74DE;
@@ -84,8 +85,8 @@ impl TryFrom<u128> for TiltColor {
 #[derive(Debug)]
 pub struct Tilt {
     pub color: TiltColor,
-    pub fahrenheit: u16,
-    pub gravity: u16,
+    pub fahrenheit: Fahrenheit,
+    pub gravity: SpecificGravity,
     pub power: i8,
 }
 
@@ -117,8 +118,8 @@ impl TryFrom<Beacon> for Tilt {
 
     fn try_from(
         Beacon {
-            major: fahrenheit,
-            minor: gravity,
+            major,
+            minor,
             uuid,
             power,
         }: Beacon,
@@ -127,8 +128,8 @@ impl TryFrom<Beacon> for Tilt {
 
         Ok(Self {
             color,
-            fahrenheit,
-            gravity,
+            fahrenheit: Fahrenheit::new(major as isize),
+            gravity: SpecificGravity::from_thousandths(minor as isize),
             power,
         })
     }
@@ -136,8 +137,39 @@ impl TryFrom<Beacon> for Tilt {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use uuid::Uuid;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn reads_tilt_beacons() {
+        let advertisement = EIRBuilder::new()
+            .with_flags(0x06)
+            .with_beacon(Beacon {
+                uuid: Uuid::from_u128(TILT_GREEN),
+                major: 68,
+                minor: 1016,
+                power: -59,
+            })
+            .build()
+            .unwrap();
+
+        match Tilt::try_from(&EIRData::from(&advertisement[..])) {
+            Ok(tilt) => {
+                assert_eq!(TiltColor::Green, tilt.color);
+                assert_eq!(1.016, tilt.gravity.specific_gravity());
+                assert_eq!(-59, tilt.power);
+            }
+
+            Err(_) => panic!("The tilt wasn't found"),
+        }
+
+        let unknown = EIRBuilder::new().with_flags(0x06).with_name("Tilt").build().unwrap();
+
+        assert!(Tilt::try_from(&EIRData::from(&unknown[..])).is_err());
+    }
 }
@@ -0,0 +1,37 @@
+use std::fmt;
+
+/// A specific gravity in thousandths, i.e. a gravity of 1.050 is 1050.
+///
+/// Brewers commonly talk about gravity "points", the thousandths above 1.000, so
+/// a gravity of 1.050 is 50 points.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct SpecificGravity(isize);
+
+impl SpecificGravity {
+    pub const fn from_thousandths(thousandths: isize) -> Self {
+        Self(thousandths)
+    }
+
+    pub const fn from_points(points: isize) -> Self {
+        Self(points + 1000)
+    }
+
+    pub const fn thousandths(self) -> isize {
+        self.0
+    }
+
+    pub const fn points(self) -> isize {
+        self.0 - 1000
+    }
+
+    pub fn specific_gravity(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+}
+
+impl fmt::Display for SpecificGravity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3}", self.specific_gravity())
+    }
+}
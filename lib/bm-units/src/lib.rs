@@ -0,0 +1,115 @@
+//! Strongly typed units of measure used throughout brew-monitor.
+//!
+//! Temperatures are held as signed hundredths of a degree celsius ([CentiCelsius](crate::CentiCelsius))
+//! or whole degrees fahrenheit ([Fahrenheit](crate::Fahrenheit)), volumes as
+//! [millilitres](crate::Millilitres), and gravity in [thousandths](crate::SpecificGravity).
+//!
+//! Every unit serializes as a plain integer. Where a value should instead be represented as
+//! a decimal number of degrees celsius or litres, for example in the Grainfather protocol
+//! types, the [celsius](crate::celsius) and [litres](crate::litres) modules can be used with
+//! `#[serde(with = "...")]`.
+
+mod temperature;
+pub use temperature::*;
+
+mod volume;
+pub use volume::*;
+
+mod gravity;
+pub use gravity::*;
+
+/// Divides, rounding to the nearest integer (with halves rounded away from zero), the
+/// numerator saturates rather than overflowing as it's rounded.
+fn div_round(numerator: isize, denominator: isize) -> isize {
+    let half = denominator / 2;
+
+    if (numerator < 0) == (denominator < 0) {
+        numerator.saturating_add(half) / denominator
+    } else {
+        numerator.saturating_sub(half) / denominator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fahrenheit_round_trips_through_celsius() {
+        for degrees in -200..=400 {
+            let fahrenheit = Fahrenheit::new(degrees);
+            assert_eq!(fahrenheit, Fahrenheit::from(CentiCelsius::from(fahrenheit)));
+        }
+    }
+
+    #[test]
+    fn converts_temperatures() {
+        assert_eq!(CentiCelsius::new(0), CentiCelsius::from(Fahrenheit::new(32)));
+        assert_eq!(CentiCelsius::new(10000), CentiCelsius::from(Fahrenheit::new(212)));
+        assert_eq!(CentiCelsius::new(-1778), CentiCelsius::from(Fahrenheit::new(0)));
+        assert_eq!(Fahrenheit::new(149), Fahrenheit::from(CentiCelsius::from_celsius(65.0)));
+        assert_eq!(6550, CentiCelsius::from_celsius(65.5).centi_celsius());
+        assert_eq!(66, CentiCelsius::from_celsius(65.5).round_to_degrees());
+        assert_eq!(-3, CentiCelsius::from_celsius(-2.5).round_to_degrees());
+    }
+
+    #[test]
+    fn saturates_extreme_temperatures() {
+        assert_eq!(CentiCelsius::new(0), CentiCelsius::from_celsius(f64::NAN));
+        assert_eq!(CentiCelsius::new(isize::MAX), CentiCelsius::from_celsius(f64::INFINITY));
+
+        let hottest = Fahrenheit::from(CentiCelsius::from_celsius(f64::INFINITY));
+        let coldest = Fahrenheit::from(CentiCelsius::from_celsius(f64::NEG_INFINITY));
+
+        assert_eq!(Fahrenheit::new(isize::MAX / 500 + 32), hottest);
+        assert_eq!(Fahrenheit::new(isize::MIN / 500 + 32), coldest);
+        assert!(CentiCelsius::from(hottest) > CentiCelsius::from_degrees(1_000_000));
+        assert!(CentiCelsius::from(Fahrenheit::new(isize::MIN)) < CentiCelsius::from_degrees(-1_000_000));
+    }
+
+    #[test]
+    fn converts_volumes() {
+        assert_eq!(Millilitres::new(13250), Millilitres::from_litres(13.25));
+        assert_eq!(13.25, Millilitres::new(13250).litres());
+        assert_eq!(Millilitres::new(18927), Millilitres::from_us_gallons(5.0));
+    }
+
+    #[test]
+    fn converts_gravities() {
+        let gravity = SpecificGravity::from_thousandths(1050);
+        assert_eq!(50, gravity.points());
+        assert_eq!(gravity, SpecificGravity::from_points(50));
+        assert_eq!(1.05, gravity.specific_gravity());
+    }
+
+    #[test]
+    fn serializes_units() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Example {
+            centi_celsius: CentiCelsius,
+            #[serde(with = "celsius")]
+            celsius: CentiCelsius,
+            #[serde(default, with = "celsius::option")]
+            maybe_celsius: Option<CentiCelsius>,
+            #[serde(with = "litres")]
+            volume: Millilitres,
+            gravity: SpecificGravity,
+        }
+
+        let example = Example {
+            centi_celsius: CentiCelsius::new(6550),
+            celsius: CentiCelsius::new(6550),
+            maybe_celsius: Some(CentiCelsius::new(7025)),
+            volume: Millilitres::new(13250),
+            gravity: SpecificGravity::from_thousandths(1050),
+        };
+
+        let json = r#"{"centi_celsius":6550,"celsius":65.5,"maybe_celsius":70.25,"volume":13.25,"gravity":1050}"#;
+        assert_eq!(json, serde_json::to_string(&example).unwrap());
+        assert_eq!(example, serde_json::from_str(json).unwrap());
+
+        let json = r#"{"centi_celsius":6550,"celsius":65.5,"volume":13.25,"gravity":1050}"#;
+        let example: Example = serde_json::from_str(json).unwrap();
+        assert_eq!(None, example.maybe_celsius);
+    }
+}
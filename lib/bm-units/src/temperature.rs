@@ -0,0 +1,124 @@
+use super::div_round;
+use std::fmt;
+
+/// A temperature in signed hundredths of a degree celsius.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct CentiCelsius(isize);
+
+impl CentiCelsius {
+    pub const fn new(centi_celsius: isize) -> Self {
+        Self(centi_celsius)
+    }
+
+    pub const fn from_degrees(degrees: isize) -> Self {
+        Self(degrees * 100)
+    }
+
+    /// Converts from degrees celsius, rounding to the nearest hundredth of a degree.
+    ///
+    /// The input should be finite, temperatures out of range saturate at the extremes
+    /// (including infinities), and NaN becomes zero.
+    pub fn from_celsius(celsius: f64) -> Self {
+        Self((celsius * 100.0).round() as isize)
+    }
+
+    pub const fn centi_celsius(self) -> isize {
+        self.0
+    }
+
+    pub fn celsius(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+
+    /// Rounds to the nearest whole degree celsius.
+    pub fn round_to_degrees(self) -> isize {
+        div_round(self.0, 100)
+    }
+}
+
+impl From<Fahrenheit> for CentiCelsius {
+    /// Converts to the nearest hundredth of a degree celsius, converting back to
+    /// fahrenheit always yields the original temperature.
+    fn from(other: Fahrenheit) -> Self {
+        Self(div_round(other.0.saturating_sub(32).saturating_mul(500), 9))
+    }
+}
+
+impl fmt::Display for CentiCelsius {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2}°C", self.celsius())
+    }
+}
+
+/// A temperature in whole degrees fahrenheit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Fahrenheit(isize);
+
+impl Fahrenheit {
+    pub const fn new(degrees: isize) -> Self {
+        Self(degrees)
+    }
+
+    pub const fn degrees(self) -> isize {
+        self.0
+    }
+}
+
+impl From<CentiCelsius> for Fahrenheit {
+    /// Converts to the nearest whole degree fahrenheit.
+    fn from(other: CentiCelsius) -> Self {
+        Self(div_round(other.0.saturating_mul(9), 500).saturating_add(32))
+    }
+}
+
+impl fmt::Display for Fahrenheit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}°F", self.0)
+    }
+}
+
+/// Serializes a [CentiCelsius](crate::CentiCelsius) as a decimal number of degrees celsius.
+pub mod celsius {
+    use super::CentiCelsius;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(value: &CentiCelsius, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f64(value.celsius())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<CentiCelsius, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        f64::deserialize(deserializer).map(CentiCelsius::from_celsius)
+    }
+
+    /// Serializes an optional [CentiCelsius](crate::CentiCelsius) as a decimal number of degrees
+    /// celsius, or null. Fields using this should also use `#[serde(default)]`.
+    pub mod option {
+        use super::CentiCelsius;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(value: &Option<CentiCelsius>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match value {
+                Some(value) => serializer.serialize_some(&value.celsius()),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<CentiCelsius>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Option::<f64>::deserialize(deserializer).map(|value| value.map(CentiCelsius::from_celsius))
+        }
+    }
+}
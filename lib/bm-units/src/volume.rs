@@ -0,0 +1,62 @@
+use std::fmt;
+
+const MILLILITRES_PER_US_GALLON: f64 = 3785.411784;
+
+/// A volume in millilitres.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Millilitres(isize);
+
+impl Millilitres {
+    pub const fn new(millilitres: isize) -> Self {
+        Self(millilitres)
+    }
+
+    /// Converts from litres, rounding to the nearest millilitre.
+    pub fn from_litres(litres: f64) -> Self {
+        Self((litres * 1000.0).round() as isize)
+    }
+
+    /// Converts from US gallons, rounding to the nearest millilitre.
+    pub fn from_us_gallons(us_gallons: f64) -> Self {
+        Self((us_gallons * MILLILITRES_PER_US_GALLON).round() as isize)
+    }
+
+    pub const fn millilitres(self) -> isize {
+        self.0
+    }
+
+    pub fn litres(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+
+    pub fn us_gallons(self) -> f64 {
+        self.0 as f64 / MILLILITRES_PER_US_GALLON
+    }
+}
+
+impl fmt::Display for Millilitres {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3}L", self.litres())
+    }
+}
+
+/// Serializes [Millilitres](crate::Millilitres) as a decimal number of litres.
+pub mod litres {
+    use super::Millilitres;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(value: &Millilitres, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f64(value.litres())
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Millilitres, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        f64::deserialize(deserializer).map(Millilitres::from_litres)
+    }
+}